@@ -0,0 +1,537 @@
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::dialect::Dialect;
+use crate::instruction::{Address, Instruction};
+use crate::parser::{Ast, Syntax};
+use crate::symbol::Reference;
+use crate::token::Token;
+use crate::{codegen, incbin, lexer, parser, repeat, symbol};
+
+use logos::Span;
+
+use std::path::Path;
+
+// A program whose symbols are resolved
+#[derive(Debug)]
+pub struct Program<'a> {
+    pub ast: Ast<'a>,
+    // Uses of symbols, which are no longer visible in `ast` after resolution
+    pub references: Vec<Reference<'a>>,
+    pub warnings: Vec<Diagnostic>,
+    // Spans of branches which were rewritten into long forms
+    pub relaxed: Vec<Span>,
+}
+
+// Parses a source program and resolves its symbols.
+// Warnings come along with the program, or before errors if it has any.
+pub fn analyze(source: &str, syntax: Syntax) -> Result<Program<'_>, Vec<Diagnostic>> {
+    analyze_in(source, syntax, Path::new(""))
+}
+
+// Same as `analyze`, but looks up INCBIN files from `directory`
+pub fn analyze_in<'a>(
+    source: &'a str,
+    syntax: Syntax,
+    directory: &Path,
+) -> Result<Program<'a>, Vec<Diagnostic>> {
+    // lexical analysis
+    let tokens = lexer::tokenize_in(source, syntax.dialect);
+    let disallowed: Vec<Diagnostic> = tokens
+        .iter()
+        .filter_map(|(token, span)| {
+            let feature = token.feature(&source[span.clone()])?;
+            (!syntax.dialect.allows(feature))
+                .then(|| Diagnostic::from_disallowed(span.clone(), source, feature, syntax.dialect))
+        })
+        .collect();
+    // The classic dialect always reads ORG as hexadecimal, so there is nothing to warn of
+    let mut warnings: Vec<Diagnostic> = Vec::new();
+    if syntax.dialect != Dialect::Classic {
+        warnings = tokens
+            .windows(2)
+            .filter_map(|pair| match pair {
+                [(Token::String(s), _), (Token::Decimal(d), span)]
+                    if syntax.is_keyword(s, "ORG") && *d >= 10 =>
+                {
+                    Some(Diagnostic::from_ambiguous_org(
+                        span.clone(),
+                        source,
+                        syntax.textbook_org(),
+                    ))
+                }
+                _ => None,
+            })
+            .collect();
+    }
+    // `'A'` is also `'A` followed by a quote, which the textbook reads as two characters. The
+    // strict dialect has no such reading.
+    if syntax.dialect == Dialect::Extended {
+        warnings.extend(tokens.iter().filter_map(|(token, span)| match token {
+            Token::Char(_) if source[span.clone()].chars().count() == 3 => {
+                Some(Diagnostic::from_ambiguous_character(span.clone(), source))
+            }
+            _ => None,
+        }));
+    }
+    // The strict dialect refuses what can be misread
+    if syntax.dialect == Dialect::Strict {
+        for warning in &mut warnings {
+            warning.severity = Severity::Error;
+        }
+    }
+
+    // Literals without LTORG are placed at END
+    let end = tokens
+        .iter()
+        .rev()
+        .find(|(token, _)| matches!(token, Token::String(s) if syntax.is_keyword(s, "END")))
+        .map_or(source.len()..source.len(), |(_, span)| span.clone());
+
+    // syntactic analysis
+    let (tokens, repeat_errs) = repeat::expand(tokens, syntax);
+    let (ast, errs) = parser::parse(tokens, syntax);
+    let has_errors = warnings
+        .iter()
+        .any(|warning| warning.severity == Severity::Error);
+    if has_errors || !disallowed.is_empty() || !repeat_errs.is_empty() || !errs.is_empty() {
+        return Err(warnings
+            .into_iter()
+            .chain(disallowed)
+            .chain(
+                repeat_errs
+                    .iter()
+                    .chain(&errs)
+                    .map(|err| Diagnostic::from_parse_error(err, source)),
+            )
+            .collect());
+    }
+    let mut ast = ast.unwrap();
+
+    let binary_errors = incbin::include(&mut ast.lines, directory, syntax.byte_order);
+    if !binary_errors.is_empty() {
+        return Err(warnings.into_iter().chain(binary_errors).collect());
+    }
+
+    // symbol resolution
+    symbol::place_literals(&mut ast.lines, end);
+    let relaxed = if syntax.relax_branches {
+        symbol::relax_branches(&mut ast.lines)
+    } else {
+        Vec::new()
+    };
+    let references = symbol::collect_references(&ast.lines);
+    let mut far_branches = Vec::new();
+    for i in symbol::out_of_range_branches(&ast.lines) {
+        if let Some(Instruction::Group6 {
+            op,
+            address: Address::Unresolved { symbol_name, .. },
+        }) = &ast.lines[i].instruction
+        {
+            let span = references
+                .iter()
+                .find(|reference| reference.line == i)
+                .map_or(ast.lines[i].span.clone(), |reference| {
+                    reference.span.clone()
+                });
+            far_branches.push(Diagnostic::from_far_branch(span, symbol_name, op));
+        }
+    }
+    // Branches left out of range after relaxation have no long form, so they cannot be assembled
+    if syntax.relax_branches {
+        for far_branch in &mut far_branches {
+            far_branch.severity = Severity::Error;
+        }
+    }
+    warnings.extend(far_branches);
+    let far_literals: Vec<Diagnostic> = symbol::out_of_range_literals(&ast.lines)
+        .into_iter()
+        .map(|i| {
+            // The literal starts at `=`
+            let span = ast.lines[i].instruction_span(source);
+            let start = source[span.clone()]
+                .find('=')
+                .map_or(span.start, |at| span.start + at);
+            Diagnostic::from_far_literal(start..span.end)
+        })
+        .collect();
+    let has_errors = warnings
+        .iter()
+        .any(|warning| warning.severity == Severity::Error);
+    if has_errors || !far_literals.is_empty() {
+        return Err(warnings.into_iter().chain(far_literals).collect());
+    }
+    symbol::resolve_symbols(&mut ast.lines);
+    let unresolved_symbols = symbol::check_unresolve_symbols(&ast.lines);
+    if !unresolved_symbols.is_empty() {
+        let symbol_table = symbol::create_symbol_table(&ast.lines);
+        let symbols: Vec<&str> = symbol_table.keys().copied().collect();
+        let mut unresolved_symbols: Vec<&str> = unresolved_symbols.into_iter().collect();
+        unresolved_symbols.sort();
+        return Err(warnings
+            .into_iter()
+            .chain(unresolved_symbols.iter().flat_map(|name| {
+                Diagnostic::from_unresolved_symbol(
+                    name,
+                    &references,
+                    &symbols,
+                    syntax.case_sensitive,
+                )
+            }))
+            .collect());
+    }
+    let overwriting: Vec<Diagnostic> = codegen::overwriting_lines(&ast.lines)
+        .into_iter()
+        .map(|(i, address, earlier)| {
+            Diagnostic::from_overwritten_address(
+                ast.lines[i].span.clone(),
+                address,
+                ast.lines[earlier].span.clone(),
+                source,
+            )
+        })
+        .collect();
+    if !overwriting.is_empty() {
+        return Err(warnings.into_iter().chain(overwriting).collect());
+    }
+
+    Ok(Program {
+        ast,
+        references,
+        warnings,
+        relaxed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{analyze, Program};
+    use crate::codegen;
+    use crate::diagnostic::Severity;
+    use crate::dialect::Dialect;
+    use crate::instruction::Instruction;
+    use crate::parser::Syntax;
+    use crate::symbol::{self, Reference};
+
+    #[test]
+    fn test_analyze() {
+        let program = analyze("TITLE T\nLOOP: B LOOP\nEND\n", Syntax::default()).unwrap();
+        assert_eq!("T", program.ast.title);
+        assert_eq!(1, program.ast.lines.len());
+        assert_eq!(
+            vec![Reference {
+                symbol: "LOOP",
+                line: 0,
+                span: 16..20,
+            }],
+            program.references
+        );
+    }
+
+    #[test]
+    fn test_analyze_warnings() {
+        let source = "TITLE T\n  ORG 100\n  HLT\n  ORG 0x200\n  ORG 9\nEND\n";
+        let program = analyze(source, Syntax::default()).unwrap();
+        assert_eq!(1, program.warnings.len());
+        assert_eq!(14..17, program.warnings[0].span);
+        assert_eq!(
+            Some(&Instruction::Org(0x100)),
+            program.ast.lines[0].instruction.as_ref()
+        );
+
+        let program = analyze(
+            source,
+            Syntax {
+                decimal_org: true,
+                ..Syntax::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(1, program.warnings.len());
+        assert_eq!(
+            Some(&Instruction::Org(100)),
+            program.ast.lines[0].instruction.as_ref()
+        );
+
+        let diagnostics =
+            analyze("TITLE T\n  ORG 10\n  B X\nEND\n", Syntax::default()).unwrap_err();
+        assert_eq!(Severity::Warning, diagnostics[0].severity);
+        assert_eq!(Severity::Error, diagnostics[1].severity);
+    }
+
+    #[test]
+    fn test_analyze_dialect() {
+        let classic = Syntax {
+            dialect: Dialect::Classic,
+            ..Syntax::default()
+        };
+        let strict = Syntax {
+            dialect: Dialect::Strict,
+            ..Syntax::default()
+        };
+
+        let source = "TITLE T\n  ORG 100\n  LC 0,'AB\nEND\n";
+        let program = analyze(source, classic).unwrap();
+        assert!(program.warnings.is_empty());
+        assert_eq!(
+            Some(&Instruction::Org(0x100)),
+            program.ast.lines[0].instruction.as_ref()
+        );
+        let diagnostics = analyze(source, strict).unwrap_err();
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            vec![
+                "ambiguous ORG operand `100`",
+                "character literals without a closing quote are not allowed in the strict dialect",
+            ],
+            messages
+        );
+        assert_eq!(Severity::Error, diagnostics[0].severity);
+
+        let source = "TITLE T\nL_1: DC 0x10,\"A\"\nEND\n";
+        let diagnostics = analyze(source, classic).unwrap_err();
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            vec![
+                "`_` in symbols are not allowed in the classic dialect",
+                "prefixed numbers are not allowed in the classic dialect",
+                "string literals are not allowed in the classic dialect",
+                "several values in DC are not allowed in the classic dialect",
+            ],
+            messages
+        );
+        assert!(analyze(source, strict).is_ok());
+
+        let source = "TITLE T\n  DCZ 1\nEND\n";
+        let diagnostics = analyze(source, classic).unwrap_err();
+        assert_eq!(
+            "DCZ, DCP and DCPZ are not allowed in the classic dialect",
+            diagnostics[0].message
+        );
+    }
+
+    #[test]
+    fn test_relax_branches() {
+        let source = "TITLE T\nLOOP: BZ FAR\n  BSR FAR\n  DS 200\nFAR: B LOOP\nEND\n";
+        let program = analyze(source, Syntax::default()).unwrap();
+        let messages: Vec<&str> = program
+            .warnings
+            .iter()
+            .map(|warning| warning.message.as_str())
+            .collect();
+        assert_eq!(
+            vec![
+                "branch to `FAR` is out of range",
+                "branch to `FAR` is out of range",
+                "branch to `LOOP` is out of range",
+            ],
+            messages
+        );
+        assert_eq!(17..20, program.warnings[0].span);
+        assert!(program.relaxed.is_empty());
+
+        let relax = Syntax {
+            relax_branches: true,
+            ..Syntax::default()
+        };
+        // BSR has no long form, so it is left out of range
+        let diagnostics = analyze(source, relax).unwrap_err();
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(Severity::Error, diagnostics[0].severity);
+        assert_eq!(27..30, diagnostics[0].span);
+
+        let source = "TITLE T\nLOOP: BZ FAR\n  DS 200\nFAR: B LOOP\nEND\n";
+        let program = analyze(source, relax).unwrap();
+        assert!(program.warnings.is_empty());
+        assert_eq!(vec![8..20, 30..41], program.relaxed);
+        let instructions: Vec<String> = program
+            .ast
+            .lines
+            .iter()
+            .map(|line| line.instruction.as_ref().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            vec![
+                "BNZ *+3",
+                "BI *+1",
+                "DC X\"00CB",
+                "DS 200",
+                "BI *+1",
+                "DC X\"0000",
+            ],
+            instructions
+        );
+        assert_eq!(Some("LOOP"), program.ast.lines[0].label);
+        assert_eq!(Some("FAR"), program.ast.lines[4].label);
+    }
+
+    #[test]
+    fn test_literal_pool() {
+        let source = "TITLE T\n  L 1,=X\"1234\"\n  L 2,=5\n  LTORG\n  L 3,=X\"1234\"\n  ST 1,=X\"1234\"\n  HLT\nEND\n";
+        let program = analyze(source, Syntax::default()).unwrap();
+        let instructions: Vec<String> = program
+            .ast
+            .lines
+            .iter()
+            .map(|line| line.instruction.as_ref().unwrap().to_string())
+            .collect();
+        // Each pool holds a value once, and the values left are placed at END
+        assert_eq!(
+            vec![
+                "L 1,*+2",
+                "L 2,*+2",
+                "LTORG",
+                "DC X\"1234",
+                "DC X\"0005",
+                "L 3,*+3",
+                "ST 1,*+2",
+                "HLT",
+                "LTORG",
+                "DC X\"1234",
+            ],
+            instructions
+        );
+        assert_eq!(77..80, program.ast.lines[9].span);
+    }
+
+    #[test]
+    fn test_layout() {
+        let source =
+            "TITLE T\n  HLT\n  ALIGN 4\n  DS 2,-1\nBUF: RESERVE 100\nX: ALIGN 4\n  HLT\nEND\n";
+        let program = analyze(source, Syntax::default()).unwrap();
+        let symbol_table = symbol::create_symbol_table(&program.ast.lines);
+        assert_eq!(Some(&6), symbol_table.get("BUF"));
+        assert_eq!(Some(&0x6A), symbol_table.get("X"));
+        // Reserved words are not in the image, while aligned ones are
+        assert_eq!(
+            vec![
+                (0x0000, 0xEF00),
+                (0x0001, 0x0000),
+                (0x0002, 0x0000),
+                (0x0003, 0x0000),
+                (0x0004, 0xFFFF),
+                (0x0005, 0xFFFF),
+                (0x006A, 0x0000),
+                (0x006B, 0x0000),
+                (0x006C, 0xEF00),
+            ],
+            codegen::generate(&program.ast.lines)
+        );
+    }
+
+    #[test]
+    fn test_layout_at_end_of_memory() {
+        // Addresses wrap around instead of overflowing
+        let source = "TITLE T\n  ORG 0xFFFF\n  DS 2\n  RESERVE 2\nX: HLT\nEND\n";
+        let program = analyze(source, Syntax::default()).unwrap();
+        assert_eq!(
+            Some(&3),
+            symbol::create_symbol_table(&program.ast.lines).get("X")
+        );
+        assert_eq!(
+            vec![(0xFFFF, 0x0000), (0x0000, 0x0000), (0x0003, 0xEF00)],
+            codegen::generate(&program.ast.lines)
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_character() {
+        let source = "TITLE T\n  DC ' '\n  DC 'A'\n  DC 'AB'\nEND\n";
+        let values = |program: &Program| -> Vec<u16> {
+            program
+                .ast
+                .lines
+                .iter()
+                .map(|line| match line.instruction {
+                    Some(Instruction::Dc { value, .. }) => value,
+                    _ => unreachable!(),
+                })
+                .collect()
+        };
+
+        // Closed literals are read, with a warning for each which the textbook reads otherwise
+        let program = analyze(source, Syntax::default()).unwrap();
+        assert_eq!(vec![0x0020, 0x0041, 0x4142], values(&program));
+        let messages: Vec<&str> = program
+            .warnings
+            .iter()
+            .map(|warning| warning.message.as_str())
+            .collect();
+        assert_eq!(
+            vec![
+                "ambiguous character literal `' '`",
+                "ambiguous character literal `'A'`",
+            ],
+            messages
+        );
+        assert_eq!(
+            Some("write `0x41` for the character or `0x4127` for the textbook reading"),
+            program.warnings[1].help.as_deref()
+        );
+
+        let strict = Syntax {
+            dialect: Dialect::Strict,
+            ..Syntax::default()
+        };
+        let program = analyze(source, strict).unwrap();
+        assert_eq!(vec![0x0020, 0x0041, 0x4142], values(&program));
+        assert!(program.warnings.is_empty());
+
+        // The classic dialect keeps the textbook reading
+        let classic = Syntax {
+            dialect: Dialect::Classic,
+            ..Syntax::default()
+        };
+        let source = "TITLE T\n  DC ' '\n  DC 'A'\n  DC 'AB\nEND\n";
+        let program = analyze(source, classic).unwrap();
+        assert_eq!(vec![0x2027, 0x4127, 0x4142], values(&program));
+        assert!(program.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_far_literal() {
+        let source = "TITLE T\n  L 1,=X\"1234\"\n  DS 300\n  HLT\nEND\n";
+        let diagnostics = analyze(source, Syntax::default()).unwrap_err();
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(Severity::Error, diagnostics[0].severity);
+        assert_eq!("literal is out of range", diagnostics[0].message);
+        assert_eq!(14..22, diagnostics[0].span);
+
+        // A pool within reach is fine
+        let source = "TITLE T\n  L 1,=X\"1234\"\n  LTORG\n  DS 300\n  HLT\nEND\n";
+        assert!(analyze(source, Syntax::default()).is_ok());
+    }
+
+    #[test]
+    fn test_overlapping_org() {
+        let source = "TITLE T\n  ORG 0x10\n  DS 4\n  ORG 0x12\n  HLT\n  NOP\nEND\n";
+        let diagnostics = analyze(source, Syntax::default()).unwrap_err();
+        // Each line of the second region is reported
+        assert_eq!(2, diagnostics.len());
+        assert_eq!("address 0012 is written twice", diagnostics[0].message);
+        assert_eq!("address 0013 is written twice", diagnostics[1].message);
+        assert_eq!(
+            "line 3 has already written a word there",
+            diagnostics[0].label
+        );
+        assert_eq!(39..42, diagnostics[0].span);
+
+        // RESERVE writes no words, so a region may be placed over it
+        let source = "TITLE T\n  ORG 0x10\n  RESERVE 4\n  ORG 0x12\n  HLT\nEND\n";
+        assert!(analyze(source, Syntax::default()).is_ok());
+    }
+
+    #[test]
+    fn test_analyze_errors() {
+        assert_eq!(
+            1,
+            analyze("TITLE T\n  LDA 1,X\nEND\n", Syntax::default())
+                .unwrap_err()
+                .len()
+        );
+        assert_eq!(
+            1,
+            analyze("TITLE T\n  B X\nEND\n", Syntax::default())
+                .unwrap_err()
+                .len()
+        );
+    }
+}
@@ -0,0 +1,282 @@
+use crate::diagnostic::{self, ErrorFormat, Severity};
+use crate::encoding::{self, InputEncoding};
+use crate::image::MemoryImage;
+use crate::parser::Syntax;
+use crate::{assembler, codegen};
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+// Result of assembling one file
+#[derive(Debug)]
+pub struct Outcome {
+    pub input: PathBuf,
+    pub output: Option<PathBuf>,
+    pub errors: usize,
+    // Diagnostics, buffered so that outputs of parallel jobs do not interleave
+    pub report: Vec<u8>,
+}
+
+impl Outcome {
+    pub fn succeeded(&self) -> bool {
+        self.errors == 0
+    }
+}
+
+// Settings shared by every file
+#[derive(Clone, Copy, Debug)]
+pub struct Options<'a> {
+    pub out_dir: Option<&'a Path>,
+    pub error_format: ErrorFormat,
+    pub input_encoding: InputEncoding,
+    pub syntax: Syntax,
+}
+
+// Whether the input names several files rather than one
+pub fn is_pattern(input: &str) -> bool {
+    input.contains(['*', '?', '[']) || Path::new(input).is_dir()
+}
+
+// Expands directories into their `*.asm` files and glob patterns into matching files
+pub fn expand_inputs(inputs: &[&str]) -> Result<Vec<PathBuf>, String> {
+    let mut paths = Vec::new();
+    for input in inputs {
+        if Path::new(input).is_dir() {
+            let pattern = Path::new(input).join("*.asm");
+            paths.extend(glob_paths(&pattern.to_string_lossy())?);
+        } else if input.contains(['*', '?', '[']) {
+            let matched = glob_paths(input)?;
+            if matched.is_empty() {
+                return Err(format!("{input}: no matching files"));
+            }
+            paths.extend(matched);
+        } else {
+            paths.push(PathBuf::from(input));
+        }
+    }
+    Ok(paths)
+}
+
+fn glob_paths(pattern: &str) -> Result<Vec<PathBuf>, String> {
+    let paths = glob::glob(pattern).map_err(|why| format!("{pattern}: {why}"))?;
+    Ok(paths
+        .filter_map(Result::ok)
+        .filter(|path| path.is_file())
+        .collect())
+}
+
+// `a.asm` is assembled into `a.b`, placed in `out_dir` if given
+pub fn output_path(input: &Path, out_dir: Option<&Path>) -> PathBuf {
+    let mut output = match out_dir {
+        Some(out_dir) => out_dir.join(input.file_name().unwrap_or(input.as_os_str())),
+        None => input.to_path_buf(),
+    };
+    output.set_extension("b");
+    output
+}
+
+// Fails if two inputs would be assembled into the same file, as `a/x.asm` and `b/x.asm` would be
+// with `out_dir`
+pub fn check_outputs(inputs: &[PathBuf], out_dir: Option<&Path>) -> Result<(), String> {
+    let mut outputs: HashMap<PathBuf, &Path> = HashMap::new();
+    for input in inputs {
+        let output = output_path(input, out_dir);
+        if let Some(other) = outputs.insert(output.clone(), input) {
+            return Err(format!(
+                "{} and {} would both be assembled into {}",
+                other.display(),
+                input.display(),
+                output.display()
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Assembles one file, never panicking so that the other files are still assembled
+pub fn assemble_file(input: &Path, options: &Options) -> Outcome {
+    let mut outcome = Outcome {
+        input: input.to_path_buf(),
+        output: None,
+        errors: 0,
+        report: Vec::new(),
+    };
+    let fail = |mut outcome: Outcome, message: String| {
+        outcome.errors = 1;
+        outcome.report = format!("{message}\n").into_bytes();
+        outcome
+    };
+
+    let (source, _) = match encoding::read_file(input, options.input_encoding) {
+        Ok(decoded) => decoded,
+        Err(why) => return fail(outcome, why),
+    };
+    let name = input.to_string_lossy();
+    let directory = input.parent().unwrap_or(Path::new(""));
+    let ast = match assembler::analyze_in(&source, options.syntax, directory) {
+        Ok(program) => {
+            diagnostic::write(
+                &program.warnings,
+                options.error_format,
+                &name,
+                &source,
+                &mut outcome.report,
+            )
+            .unwrap();
+            program.ast
+        }
+        Err(diagnostics) => {
+            diagnostic::write(
+                &diagnostics,
+                options.error_format,
+                &name,
+                &source,
+                &mut outcome.report,
+            )
+            .unwrap();
+            outcome.errors = diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity == Severity::Error)
+                .count();
+            return outcome;
+        }
+    };
+
+    let output = output_path(input, options.out_dir);
+    let image = MemoryImage::new(ast.title, codegen::generate(&ast.lines));
+    let written = File::create(&output).and_then(|file| image.write(&mut BufWriter::new(file)));
+    if let Err(why) = written {
+        return fail(outcome, format!("{}: {why}", output.display()));
+    }
+    outcome.output = Some(output);
+    outcome
+}
+
+// Assembles files on `jobs` threads, returning outcomes in the order of `inputs`
+pub fn assemble_all(inputs: &[PathBuf], options: &Options, jobs: usize) -> Vec<Outcome> {
+    let next = AtomicUsize::new(0);
+    let outcomes = Mutex::new(Vec::with_capacity(inputs.len()));
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, inputs.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(input) = inputs.get(i) else {
+                    break;
+                };
+                let outcome = assemble_file(input, options);
+                outcomes.lock().unwrap().push((i, outcome));
+            });
+        }
+    });
+
+    let mut outcomes = outcomes.into_inner().unwrap();
+    outcomes.sort_by_key(|(i, _)| *i);
+    outcomes.into_iter().map(|(_, outcome)| outcome).collect()
+}
+
+// Formats a table of results followed by the number of successes and failures
+pub fn summary(outcomes: &[Outcome]) -> String {
+    let mut table = format!("{:<8}{:>6}  FILE\n", "RESULT", "ERRORS");
+    for outcome in outcomes {
+        let result = if outcome.succeeded() { "ok" } else { "FAILED" };
+        let file = match &outcome.output {
+            Some(output) => format!("{} -> {}", outcome.input.display(), output.display()),
+            None => outcome.input.display().to_string(),
+        };
+        table += &format!("{result:<8}{:>6}  {file}\n", outcome.errors);
+    }
+
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| !outcome.succeeded())
+        .count();
+    let files = if outcomes.len() == 1 { "file" } else { "files" };
+    table
+        + &format!(
+            "{} {files}: {} succeeded, {failed} failed\n",
+            outcomes.len(),
+            outcomes.len() - failed
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        assemble_all, check_outputs, expand_inputs, is_pattern, output_path, summary, Options,
+    };
+    use crate::diagnostic::ErrorFormat;
+    use crate::encoding::InputEncoding;
+    use crate::parser::Syntax;
+
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_output_path() {
+        assert_eq!(
+            PathBuf::from("src/a.b"),
+            output_path(Path::new("src/a.asm"), None)
+        );
+        assert_eq!(
+            PathBuf::from("out/a.b"),
+            output_path(Path::new("src/a.asm"), Some(Path::new("out")))
+        );
+    }
+
+    #[test]
+    fn test_check_outputs() {
+        let inputs = [PathBuf::from("a/x.asm"), PathBuf::from("b/x.asm")];
+        assert_eq!(Ok(()), check_outputs(&inputs, None));
+        assert_eq!(
+            Err("a/x.asm and b/x.asm would both be assembled into out/x.b".to_string()),
+            check_outputs(&inputs, Some(Path::new("out")))
+        );
+    }
+
+    #[test]
+    fn test_is_pattern() {
+        assert!(is_pattern("src/*.asm"));
+        assert!(is_pattern("."));
+        assert!(!is_pattern("a.asm"));
+    }
+
+    #[test]
+    fn test_assemble_all() {
+        let dir = std::env::temp_dir().join(format!("rm1asm-batch-{}", std::process::id()));
+        let out_dir = dir.join("out");
+        fs::create_dir_all(&out_dir).unwrap();
+        fs::write(dir.join("a.asm"), "TITLE A\n  HLT\nEND\n").unwrap();
+        fs::write(dir.join("b.asm"), "TITLE B\n  B X\n  B Y\nEND\n").unwrap();
+        fs::write(dir.join("c.asm"), "TITLE C\n  RET\nEND\n").unwrap();
+
+        let inputs = expand_inputs(&[dir.to_str().unwrap()]).unwrap();
+        assert_eq!(3, inputs.len());
+        let mut options = Options {
+            out_dir: Some(&out_dir),
+            error_format: ErrorFormat::Short,
+            input_encoding: InputEncoding::Auto,
+            syntax: Syntax::default(),
+        };
+        let outcomes = assemble_all(&inputs, &options, 2);
+        let errors: Vec<usize> = outcomes.iter().map(|outcome| outcome.errors).collect();
+        assert_eq!(vec![0, 2, 0], errors);
+        assert_eq!(
+            "MM A\n0000  EF00",
+            fs::read_to_string(out_dir.join("a.b")).unwrap()
+        );
+        assert!(!out_dir.join("b.b").exists());
+        assert!(String::from_utf8_lossy(&outcomes[1].report).contains("unresolved symbol `X`"));
+        assert!(summary(&outcomes).ends_with("3 files: 2 succeeded, 1 failed\n"));
+
+        options.out_dir = None;
+        let missing = assemble_all(&[dir.join("missing.asm")], &options, 4);
+        assert_eq!(1, missing[0].errors);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
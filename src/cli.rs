@@ -0,0 +1,362 @@
+use clap::{App, AppSettings, Arg, Shell, SubCommand};
+
+// Subcommands, used to decide whether the first argument is a subcommand or an input file
+pub const SUBCOMMANDS: &[&str] = &[
+    "build",
+    "check",
+    "disasm",
+    "diff",
+    "run",
+    "fmt",
+    "symbols",
+    "xref",
+    "test-corpus",
+    "completions",
+    "help",
+];
+
+fn input<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("input")
+        .help("source code, or - for the standard input")
+        .required(true)
+}
+
+pub fn build_cli() -> App<'static, 'static> {
+    App::new(env!("CARGO_PKG_NAME"))
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("MICRO-1 machine language assembler written in Rust")
+        .after_help("`rm1asm <input>` is the same as `rm1asm build <input>`.")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(
+            Arg::with_name("error-format")
+                .help("Sets the format of error messages")
+                .long("error-format")
+                .takes_value(true)
+                .possible_values(&["human", "short"])
+                .default_value("human")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("decimal-org")
+                .help("Reads bare ORG operands such as 100 as decimal instead of hexadecimal")
+                .long("decimal-org")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("dialect")
+                .help("Sets which constructs source programs may use")
+                .long("dialect")
+                .takes_value(true)
+                .possible_values(&["classic", "extended", "strict"])
+                .default_value("extended")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("case-sensitive")
+                .help("Requires mnemonics, directives and device names in upper case")
+                .long("case-sensitive")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("relax-branches")
+                .help("Rewrites branches to targets out of range into long forms")
+                .long("relax-branches")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("byte-order")
+                .help("Sets the order of the two bytes of each word read by INCBIN")
+                .long("byte-order")
+                .takes_value(true)
+                .possible_values(&["big", "little"])
+                .default_value("big")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("input-encoding")
+                .help("Sets the encoding of source programs")
+                .long("input-encoding")
+                .takes_value(true)
+                .possible_values(&["auto", "utf-8", "sjis", "euc-jp"])
+                .default_value("auto")
+                .global(true),
+        )
+        .subcommand(
+            SubCommand::with_name("build")
+                .about("Assembles programs into memory images")
+                .arg(
+                    Arg::with_name("input")
+                        .help("source code, directory, glob pattern, or - for the standard input")
+                        .required(true)
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .help("Sets output path, or - for the standard output")
+                        .short("o")
+                        .long("output")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("out-dir")
+                        .help("Sets the directory where memory images are written")
+                        .long("out-dir")
+                        .takes_value(true)
+                        .conflicts_with("output"),
+                )
+                .arg(
+                    Arg::with_name("debug-info")
+                        .help("Writes debug information in JSON to the path")
+                        .long("debug-info")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("listing")
+                        .help(
+                            "Writes the assembly listing to the path, or - for the standard output",
+                        )
+                        .long("listing")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("map")
+                        .help("Writes the symbol map to the path, or - for the standard output")
+                        .long("map")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("map-format")
+                        .help("Sets the format of the symbol map")
+                        .long("map-format")
+                        .takes_value(true)
+                        .possible_values(&["text", "json"])
+                        .default_value("text"),
+                )
+                .arg(
+                    Arg::with_name("map-sort")
+                        .help("Sets the order of symbols in the symbol map")
+                        .long("map-sort")
+                        .takes_value(true)
+                        .possible_values(&["address", "name"])
+                        .default_value("address"),
+                )
+                .arg(
+                    Arg::with_name("jobs")
+                        .help("Sets the number of files assembled in parallel")
+                        .short("j")
+                        .long("jobs")
+                        .takes_value(true)
+                        .validator(|jobs| match jobs.parse::<usize>() {
+                            Ok(jobs) if jobs > 0 => Ok(()),
+                            _ => Err(format!("{jobs}: not a positive number")),
+                        }),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Checks a program without writing a memory image")
+                .arg(input()),
+        )
+        .subcommand(
+            SubCommand::with_name("disasm")
+                .about("Disassembles a memory image into a program")
+                .arg(
+                    Arg::with_name("input")
+                        .help("memory image, or - for the standard input")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Compares two memory images address by address")
+                .arg(Arg::with_name("old").help("memory image").required(true))
+                .arg(Arg::with_name("new").help("memory image").required(true))
+                .arg(
+                    Arg::with_name("map")
+                        .help("Annotates addresses with symbols from a symbol map")
+                        .long("map")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Assembles a program and runs it on a simulator")
+                .arg(input())
+                .arg(
+                    Arg::with_name("simulator")
+                        .help("Sets the simulator command, which takes a memory image")
+                        .long("simulator")
+                        .takes_value(true)
+                        .default_value("m1sim"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("fmt")
+                .about("Formats a program in place, or prints it when read from the standard input")
+                .arg(input())
+                .arg(
+                    Arg::with_name("check")
+                        .help("Checks whether the program is formatted without writing it")
+                        .long("check"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("symbols")
+                .about("Prints the symbol table of a program")
+                .arg(input()),
+        )
+        .subcommand(
+            SubCommand::with_name("xref")
+                .about("Prints where every symbol of a program is defined and used")
+                .arg(input()),
+        )
+        .subcommand(
+            SubCommand::with_name("test-corpus")
+                .about("Assembles every *.asm in a directory and compares it with its *.b")
+                .arg(
+                    Arg::with_name("dir")
+                        .help("directory of programs and expected memory images")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("completions")
+                .about("Prints a shell completion script")
+                .setting(AppSettings::Hidden)
+                .arg(
+                    Arg::with_name("shell")
+                        .help("shell")
+                        .required(true)
+                        .possible_values(&Shell::variants()),
+                ),
+        )
+}
+
+// Global options of `build_cli` which take a value, and those which do not
+const GLOBAL_OPTIONS: &[&str] = &[
+    "--error-format",
+    "--dialect",
+    "--byte-order",
+    "--input-encoding",
+];
+const GLOBAL_FLAGS: &[&str] = &["--decimal-org", "--case-sensitive", "--relax-branches"];
+
+// Inserts `build` when no subcommand is given, so that `rm1asm file.asm` keeps working.
+// Global options may come before the subcommand.
+pub fn normalize_args(mut args: Vec<String>) -> Vec<String> {
+    let mut i = 1;
+    while let Some(arg) = args.get(i) {
+        if GLOBAL_OPTIONS.contains(&arg.as_str()) {
+            i += 2;
+        } else if GLOBAL_FLAGS.contains(&arg.as_str())
+            || GLOBAL_OPTIONS
+                .iter()
+                .any(|option| arg.starts_with(&format!("{option}=")))
+        {
+            i += 1;
+        } else {
+            break;
+        }
+    }
+    if let Some(first) = args.get(i) {
+        let is_subcommand = SUBCOMMANDS.contains(&first.as_str());
+        let is_top_level_flag = ["-h", "--help", "-V", "--version"].contains(&first.as_str());
+        if !is_subcommand && !is_top_level_flag {
+            args.insert(i, "build".to_string());
+        }
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_cli, normalize_args};
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_normalize_args() {
+        assert_eq!(
+            args(&["rm1asm", "build", "a.asm"]),
+            normalize_args(args(&["rm1asm", "a.asm"]))
+        );
+        assert_eq!(
+            args(&["rm1asm", "build", "-o", "a.b", "a.asm"]),
+            normalize_args(args(&["rm1asm", "-o", "a.b", "a.asm"]))
+        );
+        assert_eq!(
+            args(&["rm1asm", "check", "a.asm"]),
+            normalize_args(args(&["rm1asm", "check", "a.asm"]))
+        );
+        assert_eq!(
+            args(&["rm1asm", "--help"]),
+            normalize_args(args(&["rm1asm", "--help"]))
+        );
+        assert_eq!(
+            args(&["rm1asm", "build", "-", "-o", "-"]),
+            normalize_args(args(&["rm1asm", "-", "-o", "-"]))
+        );
+        assert_eq!(args(&["rm1asm"]), normalize_args(args(&["rm1asm"])));
+        assert_eq!(
+            args(&[
+                "rm1asm",
+                "--error-format",
+                "short",
+                "--decimal-org",
+                "check",
+                "a.asm"
+            ]),
+            normalize_args(args(&[
+                "rm1asm",
+                "--error-format",
+                "short",
+                "--decimal-org",
+                "check",
+                "a.asm"
+            ]))
+        );
+        assert_eq!(
+            args(&["rm1asm", "--dialect=strict", "build", "a.asm"]),
+            normalize_args(args(&["rm1asm", "--dialect=strict", "a.asm"]))
+        );
+    }
+
+    #[test]
+    fn test_build_cli() {
+        let matches = build_cli()
+            .get_matches_from_safe(normalize_args(args(&["rm1asm", "a.asm", "-o", "a.b"])))
+            .unwrap();
+        let (name, matches) = matches.subcommand();
+        assert_eq!("build", name);
+        let matches = matches.unwrap();
+        assert_eq!(Some("a.b"), matches.value_of("output"));
+        assert_eq!(Some("human"), matches.value_of("error-format"));
+        assert_eq!(Some("auto"), matches.value_of("input-encoding"));
+        assert_eq!(Some("extended"), matches.value_of("dialect"));
+    }
+
+    #[test]
+    fn test_build_cli_multiple_inputs() {
+        let matches = build_cli()
+            .get_matches_from_safe(normalize_args(args(&[
+                "rm1asm",
+                "a.asm",
+                "b.asm",
+                "--out-dir",
+                "out",
+                "-j",
+                "2",
+            ])))
+            .unwrap();
+        let matches = matches.subcommand_matches("build").unwrap();
+        let inputs: Vec<&str> = matches.values_of("input").unwrap().collect();
+        assert_eq!(vec!["a.asm", "b.asm"], inputs);
+        assert_eq!(Some("out"), matches.value_of("out-dir"));
+        assert!(build_cli()
+            .get_matches_from_safe(args(&["rm1asm", "build", "a.asm", "-j", "0"]))
+            .is_err());
+    }
+}
@@ -1,28 +1,63 @@
 use crate::instruction::{Instruction, MachineAddress, MachineCode};
 use crate::parser::ProgramLine;
 
+use std::collections::HashMap;
+
 pub fn generate(lines: &Vec<ProgramLine>) -> Vec<(MachineAddress, MachineCode)> {
-    let mut ret = Vec::<(MachineAddress, MachineCode)>::new();
+    generate_lines(lines)
+        .into_iter()
+        .map(|(_, address, code)| (address, code))
+        .collect()
+}
+
+// Generates machine code along with the index of the line which emits each word
+pub fn generate_lines(lines: &[ProgramLine]) -> Vec<(usize, MachineAddress, MachineCode)> {
+    let mut ret = Vec::<(usize, MachineAddress, MachineCode)>::new();
 
     let mut address: MachineAddress = 0;
-    for line in lines {
+    for (i, line) in lines.iter().enumerate() {
         if let Some(instruction) = &line.instruction {
-            if let Instruction::Org(constant) = &instruction {
-                address = *constant;
-            } else if let Some(Instruction::Ds(s)) = &line.instruction {
-                for _ in 0..*s {
-                    ret.push((address, 0));
-                    address += 1;
+            match instruction {
+                Instruction::Org(constant) => address = *constant,
+                // The literals and the words of the file follow as DC
+                Instruction::Ltorg | Instruction::Incbin { .. } => {}
+                // Reserved addresses are left out of the image
+                Instruction::Reserve(size) => address = address.wrapping_add(*size),
+                Instruction::Ds(_, _) | Instruction::Align(_) => {
+                    let value = match instruction {
+                        Instruction::Ds(_, value) => *value,
+                        _ => 0,
+                    };
+                    for _ in 0..instruction.size(address) {
+                        ret.push((i, address, value));
+                        address = address.wrapping_add(1);
+                    }
                 }
-            } else {
-                match MachineCode::try_from(instruction) {
-                    Ok(c) => ret.push((address, c)),
-                    _ => panic!("{:?}: Unexpected instruction", instruction),
+                _ => {
+                    match MachineCode::try_from(instruction) {
+                        Ok(c) => ret.push((i, address, c)),
+                        _ => panic!("{:?}: Unexpected instruction", instruction),
+                    }
+                    address = address.wrapping_add(1);
                 }
-                address += 1;
             }
         }
     }
 
     ret
 }
+
+// Finds lines which write a word where an earlier line has written one, as overlapping ORG
+// regions do. Each is given with the address and the index of the earlier line.
+pub fn overwriting_lines(lines: &[ProgramLine]) -> Vec<(usize, MachineAddress, usize)> {
+    let mut written = HashMap::new();
+    let mut overwriting: Vec<(usize, MachineAddress, usize)> = Vec::new();
+    for (i, address, _) in generate_lines(lines) {
+        if let Some(earlier) = written.insert(address, i) {
+            if overwriting.last().map(|(line, _, _)| *line) != Some(i) {
+                overwriting.push((i, address, earlier));
+            }
+        }
+    }
+    overwriting
+}
@@ -0,0 +1,144 @@
+use crate::diagnostic::{self, ErrorFormat};
+use crate::encoding::{self, InputEncoding};
+use crate::image::MemoryImage;
+use crate::parser::Syntax;
+use crate::{assembler, codegen, diff};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Result of assembling a program of the corpus and comparing it with its expectation
+#[derive(Debug)]
+pub struct Case {
+    pub source: PathBuf,
+    pub passed: bool,
+    // Diagnostics or a word-level diff on failure
+    pub details: String,
+}
+
+// Assembles the program and compares it with the memory image `<name>.b` next to it
+pub fn check(
+    source: &Path,
+    format: ErrorFormat,
+    input_encoding: InputEncoding,
+    syntax: Syntax,
+) -> Case {
+    let fail = |details: String| Case {
+        source: source.to_path_buf(),
+        passed: false,
+        details,
+    };
+
+    let expected_path = source.with_extension("b");
+    let expected = match encoding::read_file(&expected_path, input_encoding) {
+        Ok((text, _)) => match text.parse::<MemoryImage>() {
+            Ok(image) => image,
+            Err(why) => return fail(format!("{}: {why}\n", expected_path.display())),
+        },
+        Err(why) => return fail(why + "\n"),
+    };
+    let program = match encoding::read_file(source, input_encoding) {
+        Ok((program, _)) => program,
+        Err(why) => return fail(why + "\n"),
+    };
+
+    let directory = source.parent().unwrap_or(Path::new(""));
+    let actual = match assembler::analyze_in(&program, syntax, directory) {
+        Ok(assembled) => {
+            MemoryImage::new(assembled.ast.title, codegen::generate(&assembled.ast.lines))
+        }
+        Err(diagnostics) => {
+            let mut report = Vec::new();
+            let name = source.to_string_lossy();
+            diagnostic::write(&diagnostics, format, &name, &program, &mut report).unwrap();
+            return fail(String::from_utf8_lossy(&report).into_owned());
+        }
+    };
+
+    let changes = diff::compare(&expected, &actual);
+    if !changes.is_empty() || expected.title != actual.title {
+        return fail(diff::format(&expected, &actual, &changes, &[]));
+    }
+    Case {
+        source: source.to_path_buf(),
+        passed: true,
+        details: String::new(),
+    }
+}
+
+// Checks every `*.asm` in the directory, sorted by name
+pub fn check_all(
+    dir: &Path,
+    format: ErrorFormat,
+    input_encoding: InputEncoding,
+    syntax: Syntax,
+) -> Result<Vec<Case>, String> {
+    let entries = fs::read_dir(dir).map_err(|why| format!("{}: {why}", dir.display()))?;
+    let mut sources: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "asm"))
+        .collect();
+    sources.sort();
+    Ok(sources
+        .iter()
+        .map(|source| check(source, format, input_encoding, syntax))
+        .collect())
+}
+
+// Formats the result of every case, with details of failures indented below them
+pub fn report(cases: &[Case]) -> String {
+    let mut text = String::new();
+    for case in cases {
+        let result = if case.passed { "PASS" } else { "FAIL" };
+        text += &format!("{result}  {}\n", case.source.display());
+        for line in case.details.lines() {
+            text += &format!("    {line}\n");
+        }
+    }
+
+    let failed = cases.iter().filter(|case| !case.passed).count();
+    text + &format!("{} passed, {failed} failed\n", cases.len() - failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_all, report};
+    use crate::diagnostic::ErrorFormat;
+    use crate::encoding::InputEncoding;
+    use crate::parser::Syntax;
+
+    use std::fs;
+
+    #[test]
+    fn test_check_all() {
+        let dir = std::env::temp_dir().join(format!("rm1asm-corpus-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.asm"), "TITLE A\n  HLT\nEND\n").unwrap();
+        fs::write(dir.join("a.b"), "MM A\n0000  EF00").unwrap();
+        fs::write(dir.join("b.asm"), "TITLE B\n  NOP\n  HLT\nEND\n").unwrap();
+        fs::write(dir.join("b.b"), "MM B\n0000  EF00").unwrap();
+        fs::write(dir.join("c.asm"), "TITLE C\n  HLT\nEND\n").unwrap();
+
+        let cases = check_all(
+            &dir,
+            ErrorFormat::Short,
+            InputEncoding::Auto,
+            Syntax::default(),
+        )
+        .unwrap();
+        let passed: Vec<bool> = cases.iter().map(|case| case.passed).collect();
+        assert_eq!(vec![true, false, false], passed);
+        assert!(cases[1].details.contains("- 0000  EF00  HLT"));
+        assert!(cases[2].details.contains("c.b"));
+        assert!(report(&cases).ends_with("1 passed, 2 failed\n"));
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(check_all(
+            &dir,
+            ErrorFormat::Short,
+            InputEncoding::Auto,
+            Syntax::default()
+        )
+        .is_err());
+    }
+}
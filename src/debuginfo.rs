@@ -0,0 +1,171 @@
+// Debug information, which maps addresses of a memory image back to the source program.
+// It is a JSON object whose addresses are 4-digit hexadecimal strings:
+//
+// - `version`: 1
+// - `file`, `title`: the source file and the title of the program
+// - `words`: `{address, line, column, kind}` for every word, where `line` and `column` are
+//   1-origin and point to the instruction, and `kind` is `code` or `data` (DC and DS)
+// - `labels`: `{name, start, end}`, the words from a label up to the next label
+// - `regions`: `{kind, start, end}`, runs of contiguous words of the same kind
+//
+// Ranges include `start` and exclude `end`.
+
+use crate::assembler::Program;
+use crate::codegen;
+use crate::diagnostic::line_column;
+use crate::instruction::{Instruction, MachineAddress};
+use crate::parser::ProgramLine;
+
+use serde_json::{json, Value};
+
+pub const VERSION: u32 = 1;
+
+fn hex(address: MachineAddress) -> String {
+    format!("{address:04X}")
+}
+
+fn kind(line: &ProgramLine) -> &'static str {
+    match line.instruction {
+        Some(
+            Instruction::Dc { .. }
+            | Instruction::Ds(_, _)
+            | Instruction::Align(_)
+            | Instruction::Reserve(_),
+        ) => "data",
+        _ => "code",
+    }
+}
+
+// A run of words, from `start` up to `end`
+struct Range<'a> {
+    name: &'a str,
+    start: MachineAddress,
+    end: MachineAddress,
+}
+
+pub fn generate(program: &Program, source: &str, file: &str) -> Value {
+    let lines = &program.ast.lines;
+
+    let mut words = Vec::new();
+    let mut labels: Vec<Range> = Vec::new();
+    let mut regions: Vec<Range> = Vec::new();
+    // A label covers the words of its line and the following unlabelled lines
+    let mut label = None;
+    let owners: Vec<Option<(usize, &str)>> = lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            if let (Some(name), Some(_)) = (line.label, &line.instruction) {
+                label = Some((i, name));
+            }
+            label
+        })
+        .collect();
+    let mut owner = None;
+    for (i, address, _) in codegen::generate_lines(lines) {
+        let line = &lines[i];
+        let (line_number, column) = line_column(source, line.instruction_span(source).start);
+        words.push(json!({
+            "address": hex(address),
+            "line": line_number,
+            "column": column,
+            "kind": kind(line),
+        }));
+
+        match owners[i] {
+            Some((_, name)) if owners[i] != owner => labels.push(Range {
+                name,
+                start: address,
+                end: address.wrapping_add(1),
+            }),
+            Some(_) => labels.last_mut().unwrap().end = address.wrapping_add(1),
+            None => {}
+        }
+        owner = owners[i];
+
+        match regions.last_mut() {
+            Some(region) if region.name == kind(line) && region.end == address => {
+                region.end = address.wrapping_add(1);
+            }
+            _ => regions.push(Range {
+                name: kind(line),
+                start: address,
+                end: address.wrapping_add(1),
+            }),
+        }
+    }
+
+    json!({
+        "version": VERSION,
+        "file": file,
+        "title": program.ast.title,
+        "words": words,
+        "labels": labels
+            .iter()
+            .map(|label| json!({
+                "name": label.name,
+                "start": hex(label.start),
+                "end": hex(label.end),
+            }))
+            .collect::<Vec<Value>>(),
+        "regions": regions
+            .iter()
+            .map(|region| json!({
+                "kind": region.name,
+                "start": hex(region.start),
+                "end": hex(region.end),
+            }))
+            .collect::<Vec<Value>>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::generate;
+    use crate::assembler::analyze;
+    use crate::parser::Syntax;
+
+    use serde_json::json;
+
+    #[test]
+    fn test_generate() {
+        let source = "TITLE T
+LOOP:   L     1,X
+        BNZ   LOOP
+X:      DC    0
+BUF:    DS    2
+NEXT:   ORG   0x10
+        HLT
+        END
+";
+        let program = analyze(source, Syntax::default()).unwrap();
+        let debug_info = generate(&program, source, "t.asm");
+        assert_eq!(1, debug_info["version"]);
+        assert_eq!("t.asm", debug_info["file"]);
+        assert_eq!(
+            json!({"address": "0001", "line": 3, "column": 9, "kind": "code"}),
+            debug_info["words"][1]
+        );
+        assert_eq!(
+            json!({"address": "0004", "line": 5, "column": 9, "kind": "data"}),
+            debug_info["words"][4]
+        );
+        assert_eq!(
+            json!([
+                {"name": "LOOP", "start": "0000", "end": "0002"},
+                {"name": "X", "start": "0002", "end": "0003"},
+                {"name": "BUF", "start": "0003", "end": "0005"},
+                {"name": "NEXT", "start": "0010", "end": "0011"},
+            ]),
+            debug_info["labels"]
+        );
+        assert_eq!(
+            json!([
+                {"kind": "code", "start": "0000", "end": "0002"},
+                {"kind": "data", "start": "0002", "end": "0005"},
+                {"kind": "code", "start": "0010", "end": "0011"},
+            ]),
+            debug_info["regions"]
+        );
+    }
+}
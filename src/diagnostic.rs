@@ -0,0 +1,666 @@
+use crate::dialect::{Dialect, Feature};
+use crate::instruction::Opecode6;
+use crate::parser;
+use crate::symbol::Reference;
+use crate::token::Token;
+use crate::{lexer, symbol};
+
+use ariadne::{Color, Label, Report, ReportKind, Source};
+use chumsky::error::{Simple, SimpleReason};
+use logos::Span;
+
+use std::io::{self, Write};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ErrorFormat {
+    Human,
+    Short,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "short" => Ok(ErrorFormat::Short),
+            _ => Err(format!("{s}: Unknown error format")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Severity {
+    Error,
+    // A warning does not stop assembling
+    Warning,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+    pub label: String,
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn from_parse_error(err: &Simple<Token>, source: &str) -> Self {
+        let span = err.span();
+        let found = match err.found() {
+            Some(Token::Eol) => "end of line".to_string(),
+            Some(_) => format!("`{}`", &source[span.clone()]),
+            // Custom errors cover the tokens they reject without a single found token
+            None if !span.is_empty() => format!("`{}`", &source[span.clone()]),
+            None => "end of input".to_string(),
+        };
+
+        if let Some(Token::Error) = err.found() {
+            return Diagnostic::from_invalid_token(span, source);
+        }
+        if let SimpleReason::Custom(message) = err.reason() {
+            return Diagnostic {
+                severity: Severity::Error,
+                span,
+                message: message.clone(),
+                label: format!("found {found}"),
+                help: None,
+            };
+        }
+        // Nothing can fail at the end of input once END has been read
+        if err.found().is_none() {
+            return Diagnostic {
+                severity: Severity::Error,
+                span,
+                message: "missing END".to_string(),
+                label: "END is expected at the end of program".to_string(),
+                help: None,
+            };
+        }
+
+        // A word where a mnemonic should be is an unknown instruction
+        if let Some(Token::String(s)) = err.found() {
+            if is_mnemonic_position(source, span.start)
+                && !err.expected().any(|e| *e == Some(Token::String("TITLE")))
+            {
+                return Diagnostic {
+                    severity: Severity::Error,
+                    span,
+                    message: format!("unknown instruction `{s}`"),
+                    label: "expected an instruction".to_string(),
+                    help: did_you_mean(s, &parser::mnemonics(), true),
+                };
+            }
+        }
+
+        let previous = lexer::tokenize(&source[..span.start.min(source.len())]).pop();
+        // The parser tried to read a line head as a label, so it was not a known mnemonic
+        if let Some((Token::String(s), previous_span)) = &previous {
+            if is_mnemonic_position(source, previous_span.start)
+                && err.label().is_none()
+                && err.expected().any(|e| *e == Some(Token::Colon))
+            {
+                return if s.eq_ignore_ascii_case("END") {
+                    Diagnostic {
+                        severity: Severity::Error,
+                        span,
+                        message: "unexpected line after END".to_string(),
+                        label: format!("found {found}"),
+                        help: None,
+                    }
+                } else {
+                    Diagnostic {
+                        severity: Severity::Error,
+                        span: previous_span.clone(),
+                        message: format!("unknown instruction `{s}`"),
+                        label: "expected an instruction, or a label followed by `:`".to_string(),
+                        help: did_you_mean(s, &parser::mnemonics(), true),
+                    }
+                };
+            }
+        }
+
+        let expected = if let Some(label) = err.label() {
+            label.to_string()
+        } else {
+            let mut expected: Vec<String> = err
+                .expected()
+                .map(|e| match e {
+                    Some(Token::Eol) => "end of line".to_string(),
+                    Some(t) => format!("`{t}`"),
+                    None => "end of input".to_string(),
+                })
+                .collect();
+            expected.sort();
+            if expected.is_empty() {
+                "nothing".to_string()
+            } else {
+                expected.join(" or ")
+            }
+        };
+        // A mnemonic or symbol just before the error names the context, e.g. "after LEA"
+        let context = match previous {
+            Some((Token::String(s), _)) => format!(" after {s}"),
+            _ => "".to_string(),
+        };
+
+        Diagnostic {
+            severity: Severity::Error,
+            span,
+            message: format!("expected {expected}{context}"),
+            label: format!("found {found}"),
+            help: None,
+        }
+    }
+
+    fn from_invalid_token(span: Span, source: &str) -> Self {
+        let text = &source[span.clone()];
+        let is_number = text.starts_with(|c: char| c.is_ascii_digit())
+            || ["X\"", "O\"", "B\""]
+                .iter()
+                .any(|prefix| text.starts_with(prefix));
+        let message = if is_number {
+            format!("number `{text}` does not fit in 16 bits")
+        } else {
+            format!("invalid character `{text}`")
+        };
+        Diagnostic {
+            severity: Severity::Error,
+            span,
+            message,
+            label: "this token is not recognized".to_string(),
+            help: None,
+        }
+    }
+
+    // Warns of an ORG operand which the textbook syntax reads as hexadecimal but otherwise is
+    // decimal
+    pub fn from_ambiguous_org(span: Span, source: &str, textbook: bool) -> Self {
+        let text = &source[span.clone()];
+        let digits = text.replace('_', "");
+        let decimal: u32 = digits.parse().unwrap_or(0);
+        let hexadecimal = u32::from_str_radix(&digits, 16).unwrap_or(0);
+        let label = if textbook {
+            format!("read as hexadecimal, 0x{hexadecimal:X}")
+        } else {
+            format!("read as decimal, 0x{decimal:X}")
+        };
+        Diagnostic {
+            severity: Severity::Warning,
+            span,
+            message: format!("ambiguous ORG operand `{text}`"),
+            label,
+            help: Some(format!(
+                "write `0x{hexadecimal:X}` for hexadecimal or `0x{decimal:X}` for decimal"
+            )),
+        }
+    }
+
+    // Warns of `'A'`, which the textbook reads as `'A` followed by a quote
+    pub fn from_ambiguous_character(span: Span, source: &str) -> Self {
+        let text = &source[span.clone()];
+        let character = text.chars().nth(1).map_or(0, u32::from);
+        let legacy = (character << 8) | u32::from(b'\'');
+        Diagnostic {
+            severity: Severity::Warning,
+            span,
+            message: format!("ambiguous character literal `{text}`"),
+            label: format!("read as a closed literal, 0x{character:X}"),
+            help: Some(format!(
+                "write `0x{character:X}` for the character or `0x{legacy:X}` for the textbook reading"
+            )),
+        }
+    }
+
+    // Reports a construct which the selected dialect does not accept
+    pub fn from_disallowed(span: Span, source: &str, feature: Feature, dialect: Dialect) -> Self {
+        let allowing: Vec<String> = [Dialect::Classic, Dialect::Extended, Dialect::Strict]
+            .iter()
+            .filter(|dialect| dialect.allows(feature))
+            .map(|dialect| format!("`--dialect {dialect}`"))
+            .collect();
+        Diagnostic {
+            severity: Severity::Error,
+            span: span.clone(),
+            message: dialect.disallowed(feature),
+            label: format!("`{}` is not accepted here", &source[span]),
+            help: Some(format!("use {}", allowing.join(" or "))),
+        }
+    }
+
+    // Reports a file which INCBIN cannot include
+    pub fn from_binary_file(span: Span, why: String) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            span,
+            message: why,
+            label: "the file is included here".to_string(),
+            help: None,
+        }
+    }
+
+    // Reports a literal whose pool is too far for the 8-bit displacement
+    pub fn from_far_literal(span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            span,
+            message: "literal is out of range".to_string(),
+            label: "its pool is more than 128 words away".to_string(),
+            help: Some("add an LTORG closer to it".to_string()),
+        }
+    }
+
+    // Reports a line whose words land on ones already written, which an image cannot hold
+    pub fn from_overwritten_address(span: Span, address: u16, earlier: Span, source: &str) -> Self {
+        let (line, _) = line_column(source, earlier.start);
+        Diagnostic {
+            severity: Severity::Error,
+            span,
+            message: format!("address {address:04X} is written twice"),
+            label: format!("line {line} has already written a word there"),
+            help: Some("move the ORG so that the regions do not overlap".to_string()),
+        }
+    }
+
+    // Warns of a branch whose displacement does not fit in 8 bits
+    pub fn from_far_branch(span: Span, symbol_name: &str, op: &Opecode6) -> Self {
+        let help = if symbol::has_long_form(op) {
+            "use `--relax-branches` to rewrite it into a long form".to_string()
+        } else {
+            format!("{op} has no long form; use JMP or move `{symbol_name}` closer")
+        };
+        Diagnostic {
+            severity: Severity::Warning,
+            span,
+            message: format!("branch to `{symbol_name}` is out of range"),
+            label: "more than 128 words away".to_string(),
+            help: Some(help),
+        }
+    }
+
+    // Reports every use of a symbol which is not defined in the program
+    pub fn from_unresolved_symbol(
+        name: &str,
+        references: &[Reference],
+        symbols: &[&str],
+        case_sensitive: bool,
+    ) -> Vec<Self> {
+        references
+            .iter()
+            .filter(|reference| reference.symbol == name)
+            .map(|reference| Diagnostic {
+                severity: Severity::Error,
+                span: reference.span.clone(),
+                message: format!("unresolved symbol `{name}`"),
+                label: "this symbol is not defined".to_string(),
+                help: did_you_mean(name, symbols, !case_sensitive),
+            })
+            .collect()
+    }
+}
+
+// Suggests the candidates closest to the word, ignoring case if `fold_case`
+fn did_you_mean(word: &str, candidates: &[&str], fold_case: bool) -> Option<String> {
+    let fold = |s: &str| match fold_case {
+        true => s.to_ascii_uppercase(),
+        false => s.to_string(),
+    };
+    let word = fold(word);
+    let threshold = (word.chars().count() / 3).max(1);
+    let distances: Vec<(usize, &str)> = candidates
+        .iter()
+        .map(|candidate| (edit_distance(&word, &fold(candidate)), *candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .collect();
+    let nearest = distances.iter().map(|(distance, _)| *distance).min()?;
+    let mut suggestions: Vec<String> = distances
+        .iter()
+        .filter(|(distance, _)| *distance == nearest)
+        .map(|(_, candidate)| format!("`{candidate}`"))
+        .collect();
+    suggestions.sort();
+    suggestions.dedup();
+    suggestions.truncate(3);
+    Some(format!("did you mean {}?", suggestions.join(" or ")))
+}
+
+// Edit distance between two strings, counting a swap of adjacent characters as one edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+// Whether the word is read as a single symbol by the lexer
+fn is_identifier(word: &str) -> bool {
+    matches!(lexer::tokenize(word)[..], [(Token::String(s), _)] if s == word)
+}
+
+// Whether the offset is at the head of a line, or just after a label
+fn is_mnemonic_position(source: &str, offset: usize) -> bool {
+    let head = source[..offset].rsplit('\n').next().unwrap_or("").trim();
+    head.is_empty()
+        || head
+            .strip_suffix(':')
+            .is_some_and(|label| is_identifier(label.trim_end()))
+}
+
+// Returns the 1-origin line and column of the byte offset in the source.
+// The column is counted in characters, as editors do.
+pub fn line_column(source: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(source.len());
+    let head = &source[..offset];
+    let line = head.matches('\n').count() + 1;
+    let line_start = head.rfind('\n').map_or(0, |i| i + 1);
+    let column = head[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+// Converts a span of bytes into a span of characters, which ariadne counts in
+fn char_span(source: &str, span: &Span) -> Span {
+    let chars = |offset: usize| source[..offset.min(source.len())].chars().count();
+    chars(span.start)..chars(span.end)
+}
+
+pub fn print(diagnostics: &[Diagnostic], format: ErrorFormat, path: &str, source: &str) {
+    write(diagnostics, format, path, source, &mut io::stderr()).unwrap();
+}
+
+// Writes diagnostics to `w`, so that they can be buffered and printed later
+pub fn write<W: Write>(
+    diagnostics: &[Diagnostic],
+    format: ErrorFormat,
+    path: &str,
+    source: &str,
+    w: &mut W,
+) -> io::Result<()> {
+    for diagnostic in diagnostics {
+        match format {
+            ErrorFormat::Human => {
+                let span = char_span(source, &diagnostic.span);
+                let (kind, color) = match diagnostic.severity {
+                    Severity::Error => (ReportKind::Error, Color::Red),
+                    Severity::Warning => (ReportKind::Warning, Color::Yellow),
+                };
+                let mut report = Report::build(kind, path, span.start)
+                    .with_message(&diagnostic.message)
+                    .with_label(
+                        Label::new((path, span))
+                            .with_message(&diagnostic.label)
+                            .with_color(color),
+                    );
+                if let Some(help) = &diagnostic.help {
+                    report = report.with_help(help);
+                }
+                report
+                    .finish()
+                    .write((path, Source::from(source)), &mut *w)?;
+            }
+            ErrorFormat::Short => {
+                let (line, column) = line_column(source, diagnostic.span.start);
+                let severity = match diagnostic.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                };
+                writeln!(
+                    w,
+                    "{path}:{line}:{column}: {severity}: {}",
+                    diagnostic.message
+                )?;
+                if let Some(help) = &diagnostic.help {
+                    writeln!(w, "{path}:{line}:{column}: note: {help}")?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Diagnostic;
+    use super::ErrorFormat;
+    use super::{char_span, line_column};
+    use super::{did_you_mean, edit_distance};
+    use crate::dialect::{Dialect, Feature};
+    use crate::parser::Syntax;
+    use crate::{lexer, parser, symbol};
+
+    fn messages(source: &str) -> Vec<String> {
+        messages_in(source, Syntax::default())
+    }
+
+    fn messages_in(source: &str, syntax: Syntax) -> Vec<String> {
+        let (_, errs) = parser::parse(lexer::tokenize(source), syntax);
+        errs.iter()
+            .map(|err| Diagnostic::from_parse_error(err, source).message)
+            .collect()
+    }
+
+    #[test]
+    fn test_line_column() {
+        let source = "TITLE T\n  ADD 0,1\n\tL 1,ÄB\nEND";
+        assert_eq!((1, 1), line_column(source, 0));
+        assert_eq!((1, 7), line_column(source, 6));
+        assert_eq!((2, 3), line_column(source, 10));
+        assert_eq!((3, 6), line_column(source, 23));
+        assert_eq!((3, 7), line_column(source, 25));
+        assert_eq!((4, 4), line_column(source, source.len()));
+    }
+
+    #[test]
+    fn test_char_span() {
+        let source = "TITLE T ; 合計\n  B X\nEND";
+        let start = source.find('X').unwrap();
+        assert_eq!(17..18, char_span(source, &(start..start + 1)));
+        assert_eq!(0..5, char_span(source, &(0..5)));
+    }
+
+    #[test]
+    fn test_error_format() {
+        assert_eq!(Ok(ErrorFormat::Human), "human".parse());
+        assert_eq!(Ok(ErrorFormat::Short), "short".parse());
+        assert!("gcc".parse::<ErrorFormat>().is_err());
+    }
+
+    #[test]
+    fn test_expected_register() {
+        let source = "TITLE T\n  LEA X\nEND\n";
+        assert_eq!(vec!["expected register 0-3 after LEA"], messages(source));
+    }
+
+    #[test]
+    fn test_expected_comma() {
+        let source = "TITLE T\n  L 1 FOO\nEND\n";
+        assert_eq!(
+            vec!["expected `,` between register and address"],
+            messages(source)
+        );
+    }
+
+    #[test]
+    fn test_missing_title() {
+        let source = "T\n  NOP\nEND\n";
+        assert_eq!(vec!["expected `TITLE`"], messages(source));
+    }
+
+    #[test]
+    fn test_missing_end() {
+        let source = "TITLE T\n  NOP\n";
+        assert_eq!(vec!["missing END"], messages(source));
+    }
+
+    #[test]
+    fn test_unknown_instruction() {
+        let source = "TITLE T\n  LDA 1,X\nEND\n";
+        assert_eq!(vec!["unknown instruction `LDA`"], messages(source));
+        let source = "TITLE T\nFOO: BRZ X\nEND\n";
+        assert_eq!(vec!["unknown instruction `BRZ`"], messages(source));
+        let source = "TITLE T\nL_1: BRZ X\nEND\n";
+        let (_, errs) = parser::parse(lexer::tokenize(source), Syntax::default());
+        let diagnostic = Diagnostic::from_parse_error(&errs[0], source);
+        assert_eq!("unknown instruction `BRZ`", diagnostic.message);
+        assert_eq!(
+            Some("did you mean `BNZ` or `BZ`?".to_string()),
+            diagnostic.help
+        );
+    }
+
+    #[test]
+    fn test_lower_case_instruction() {
+        let source = "TITLE T\n  add 0,1\nEND\n";
+        let syntax = Syntax {
+            case_sensitive: true,
+            ..Syntax::default()
+        };
+        let (_, errs) = parser::parse(lexer::tokenize(source), syntax);
+        let diagnostic = Diagnostic::from_parse_error(&errs[0], source);
+        assert_eq!("unknown instruction `add`", diagnostic.message);
+        assert_eq!(Some("did you mean `ADD`?".to_string()), diagnostic.help);
+        assert!(messages(source).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_string() {
+        let source = "TITLE T\n  DC \"a\\qb\"\nEND\n";
+        assert_eq!(vec!["unknown escape sequence `\\q`"], messages(source));
+        let source = "TITLE T\n  DC \"合計\"\nEND\n";
+        assert_eq!(
+            vec!["character `合` does not fit in 8 bits"],
+            messages(source)
+        );
+        let source = "TITLE T\n  LC 0,'合'\nEND\n";
+        assert_eq!(
+            vec!["character `合` does not fit in 8 bits"],
+            messages(source)
+        );
+    }
+
+    #[test]
+    fn test_textbook_org() {
+        let syntax = Syntax {
+            decimal_org: true,
+            ..Syntax::default()
+        };
+        let source = "TITLE T\n  ORG A98\nEND\n";
+        assert!(messages(source).is_empty());
+        assert_eq!(
+            vec!["`A98` is hexadecimal only in the textbook syntax; write `0xA98`"],
+            messages_in(source, syntax)
+        );
+        let source = "TITLE T\n  ORG 1FF\nEND\n";
+        assert_eq!(
+            vec!["`1FF` is hexadecimal only in the textbook syntax; write `0x1FF`"],
+            messages_in(source, syntax)
+        );
+    }
+
+    #[test]
+    fn test_disallowed() {
+        let source = "TITLE T\n  DC \"A\"\nEND\n";
+        let diagnostic =
+            Diagnostic::from_disallowed(13..16, source, Feature::StringLiteral, Dialect::Classic);
+        assert_eq!(
+            "string literals are not allowed in the classic dialect",
+            diagnostic.message
+        );
+        assert_eq!("`\"A\"` is not accepted here", diagnostic.label);
+        assert_eq!(
+            Some("use `--dialect extended` or `--dialect strict`".to_string()),
+            diagnostic.help
+        );
+    }
+
+    #[test]
+    fn test_invalid_token() {
+        let source = "TITLE T\n  LC 1,65536\nEND\n";
+        assert_eq!(
+            vec!["number `65536` does not fit in 16 bits"],
+            messages(source)
+        );
+        let source = "TITLE T\n  LC 2,X\"FFFFF\nEND\n";
+        assert_eq!(
+            vec!["number `X\"FFFFF` does not fit in 16 bits"],
+            messages(source)
+        );
+        let source = "TITLE T\n  NOP @\nEND\n";
+        assert_eq!(vec!["invalid character `@`"], messages(source));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(0, edit_distance("ADD", "ADD"));
+        assert_eq!(1, edit_distance("BRZ", "BZ"));
+        assert_eq!(1, edit_distance("LDA", "LA"));
+        assert_eq!(2, edit_distance("LDA", "L"));
+        assert_eq!(1, edit_distance("LOPO", "LOOP"));
+        assert_eq!(3, edit_distance("", "HLT"));
+    }
+
+    #[test]
+    fn test_did_you_mean() {
+        let mnemonics = &crate::parser::mnemonics();
+        assert_eq!(
+            Some("did you mean `BNZ` or `BZ`?".to_string()),
+            did_you_mean("BRZ", mnemonics, true)
+        );
+        assert_eq!(
+            Some("did you mean `LA` or `LEA`?".to_string()),
+            did_you_mean("LDA", mnemonics, true)
+        );
+        assert_eq!(
+            Some("did you mean `ADD`?".to_string()),
+            did_you_mean("add", mnemonics, true)
+        );
+        assert_eq!(
+            Some("did you mean `REG`?".to_string()),
+            did_you_mean("REGG", mnemonics, true)
+        );
+        assert_eq!(None, did_you_mean("FOOBAR", mnemonics, true));
+    }
+
+    #[test]
+    fn test_unresolved_symbol() {
+        let source = "TITLE LOOP\nLOOP: B LOPO\n  L 1,LOPO\nEND\n";
+        let lines = parser::parse(lexer::tokenize(source), Syntax::default())
+            .0
+            .unwrap()
+            .lines;
+        let references = symbol::collect_references(&lines);
+        let diagnostics = Diagnostic::from_unresolved_symbol("LOPO", &references, &["LOOP"], false);
+        assert_eq!(2, diagnostics.len());
+        assert_eq!(19..23, diagnostics[0].span);
+        assert_eq!(30..34, diagnostics[1].span);
+        assert_eq!(
+            Some("did you mean `LOOP`?".to_string()),
+            diagnostics[0].help
+        );
+
+        // Case is significant under case-sensitive matching, so `loop` is not close to `LOPO`
+        let diagnostics = Diagnostic::from_unresolved_symbol("LOPO", &references, &["loop"], false);
+        assert_eq!(
+            Some("did you mean `loop`?".to_string()),
+            diagnostics[0].help
+        );
+        let diagnostics = Diagnostic::from_unresolved_symbol("LOPO", &references, &["loop"], true);
+        assert_eq!(None, diagnostics[0].help);
+    }
+}
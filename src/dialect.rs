@@ -0,0 +1,143 @@
+use std::fmt;
+use std::str::FromStr;
+
+// Set of constructs accepted in source programs
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Dialect {
+    // Only what the textbook assembler accepts
+    Classic,
+    // Everything
+    #[default]
+    Extended,
+    // Extensions without the textbook forms which can be misread
+    Strict,
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "classic" => Ok(Dialect::Classic),
+            "extended" => Ok(Dialect::Extended),
+            "strict" => Ok(Dialect::Strict),
+            _ => Err(format!("{s}: Unknown dialect")),
+        }
+    }
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Dialect::Classic => write!(f, "classic"),
+            Dialect::Extended => write!(f, "extended"),
+            Dialect::Strict => write!(f, "strict"),
+        }
+    }
+}
+
+// A construct which only some dialects accept
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Feature {
+    // `0x1F`, `0o17`, `0b101`, `$1F` and `1FH`
+    PrefixedNumber,
+    // `1_000`
+    DigitSeparator,
+    // `1FF`, which is only read by ORG
+    BareHexadecimal,
+    // `'AB` without a closing quote
+    LegacyCharacters,
+    // `'A'` and `'\n'`
+    CharacterLiteral,
+    // `"Hello"`
+    StringLiteral,
+    // `DC 1,2,3`
+    DataList,
+    // `DCZ`, `DCP` and `DCPZ`
+    DataDirective,
+    // `LOOP_1`
+    UnderscoreSymbol,
+    // `R1` and aliases defined by REG
+    RegisterName,
+    // `CLR`, `INC`, `DEC`, `LI` and `JMP`
+    PseudoInstruction,
+    // `L 1,=X"1234"` and LTORG
+    LiteralPool,
+    // `ALIGN 8`, `FILL 4,-1` and `RESERVE 100`
+    LayoutDirective,
+    // `DS 4,-1`
+    FillValue,
+    // `REPT 4` and `IRP R,<1,2>`
+    RepeatBlock,
+    // `INCBIN "table.bin"`
+    BinaryInclusion,
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Feature::PrefixedNumber => write!(f, "prefixed numbers"),
+            Feature::DigitSeparator => write!(f, "`_` in numbers"),
+            Feature::BareHexadecimal => write!(f, "hexadecimal numbers without a prefix"),
+            Feature::LegacyCharacters => write!(f, "character literals without a closing quote"),
+            Feature::CharacterLiteral => write!(f, "closed character literals"),
+            Feature::StringLiteral => write!(f, "string literals"),
+            Feature::DataList => write!(f, "several values in DC"),
+            Feature::DataDirective => write!(f, "DCZ, DCP and DCPZ"),
+            Feature::UnderscoreSymbol => write!(f, "`_` in symbols"),
+            Feature::RegisterName => write!(f, "register names"),
+            Feature::PseudoInstruction => write!(f, "pseudo-instructions"),
+            Feature::LiteralPool => write!(f, "literals"),
+            Feature::LayoutDirective => write!(f, "ALIGN, FILL and RESERVE"),
+            Feature::FillValue => write!(f, "fill values in DS"),
+            Feature::RepeatBlock => write!(f, "REPT and IRP"),
+            Feature::BinaryInclusion => write!(f, "INCBIN directives"),
+        }
+    }
+}
+
+impl Dialect {
+    pub fn allows(self, feature: Feature) -> bool {
+        match self {
+            Dialect::Classic => matches!(
+                feature,
+                Feature::BareHexadecimal | Feature::LegacyCharacters
+            ),
+            Dialect::Extended => true,
+            Dialect::Strict => !matches!(
+                feature,
+                Feature::BareHexadecimal | Feature::LegacyCharacters
+            ),
+        }
+    }
+
+    pub fn disallowed(self, feature: Feature) -> String {
+        format!("{feature} are not allowed in the {self} dialect")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dialect, Feature};
+
+    #[test]
+    fn test_dialect() {
+        assert_eq!(Ok(Dialect::Classic), "classic".parse());
+        assert_eq!(Ok(Dialect::Strict), "strict".parse());
+        assert!("modern".parse::<Dialect>().is_err());
+        assert_eq!("extended", Dialect::default().to_string());
+    }
+
+    #[test]
+    fn test_allows() {
+        assert!(Dialect::Classic.allows(Feature::LegacyCharacters));
+        assert!(!Dialect::Classic.allows(Feature::StringLiteral));
+        assert!(Dialect::Extended.allows(Feature::LegacyCharacters));
+        assert!(!Dialect::Strict.allows(Feature::BareHexadecimal));
+        assert!(Dialect::Strict.allows(Feature::DataList));
+        assert_eq!(
+            "string literals are not allowed in the classic dialect",
+            Dialect::Classic.disallowed(Feature::StringLiteral)
+        );
+    }
+}
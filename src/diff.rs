@@ -0,0 +1,142 @@
+use crate::disasm::disassemble_word;
+use crate::image::MemoryImage;
+use crate::instruction::{MachineAddress, MachineCode};
+
+use std::collections::BTreeMap;
+
+// A difference between two memory images at an address
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Change {
+    Added(MachineAddress, MachineCode),
+    Removed(MachineAddress, MachineCode),
+    Changed(MachineAddress, MachineCode, MachineCode),
+}
+
+// Compares two images address by address, in the order of addresses
+pub fn compare(old: &MemoryImage, new: &MemoryImage) -> Vec<Change> {
+    let mut words: BTreeMap<MachineAddress, (Option<MachineCode>, Option<MachineCode>)> =
+        BTreeMap::new();
+    for (address, code) in &old.words {
+        words.entry(*address).or_default().0 = Some(*code);
+    }
+    for (address, code) in &new.words {
+        words.entry(*address).or_default().1 = Some(*code);
+    }
+
+    words
+        .into_iter()
+        .filter_map(|(address, words)| match words {
+            (Some(old), Some(new)) if old != new => Some(Change::Changed(address, old, new)),
+            (Some(old), None) => Some(Change::Removed(address, old)),
+            (None, Some(new)) => Some(Change::Added(address, new)),
+            _ => None,
+        })
+        .collect()
+}
+
+// Names an address after the nearest symbol at or before it, such as `LOOP+2`
+fn annotate(address: MachineAddress, symbols: &[(MachineAddress, String)]) -> Option<String> {
+    let (symbol_address, name) = symbols
+        .iter()
+        .filter(|(symbol_address, _)| *symbol_address <= address)
+        .max_by_key(|(symbol_address, _)| *symbol_address)?;
+    match address - symbol_address {
+        0 => Some(name.clone()),
+        offset => Some(format!("{name}+{offset}")),
+    }
+}
+
+fn format_word(
+    sign: char,
+    address: MachineAddress,
+    code: MachineCode,
+    symbols: &[(MachineAddress, String)],
+) -> String {
+    let line = format!(
+        "{sign} {address:04X}  {code:04X}  {:<24}",
+        disassemble_word(code)
+    );
+    match annotate(address, symbols) {
+        Some(symbol) => format!("{line}; {symbol}\n"),
+        None => line.trim_end().to_string() + "\n",
+    }
+}
+
+// Formats changes as `-` and `+` lines with their instructions, followed by a summary
+pub fn format(
+    old: &MemoryImage,
+    new: &MemoryImage,
+    changes: &[Change],
+    symbols: &[(MachineAddress, String)],
+) -> String {
+    let mut text = String::new();
+    if old.title != new.title {
+        text += &format!("- MM {}\n+ MM {}\n", old.title, new.title);
+    }
+
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for change in changes {
+        match *change {
+            Change::Added(address, code) => {
+                added += 1;
+                text += &format_word('+', address, code, symbols);
+            }
+            Change::Removed(address, code) => {
+                removed += 1;
+                text += &format_word('-', address, code, symbols);
+            }
+            Change::Changed(address, old, new) => {
+                changed += 1;
+                text += &format_word('-', address, old, symbols);
+                text += &format_word('+', address, new, symbols);
+            }
+        }
+    }
+    text + &format!("{changed} changed, {added} added, {removed} removed\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare, format, Change};
+    use crate::image::MemoryImage;
+
+    #[test]
+    fn test_compare() {
+        let old = MemoryImage::new("T", vec![(0x0000, 0x9102), (0x0001, 0xEF00), (0x0002, 1)]);
+        let new = MemoryImage::new("T", vec![(0x0000, 0x9103), (0x0001, 0xEF00), (0x0003, 2)]);
+        let expected = vec![
+            Change::Changed(0x0000, 0x9102, 0x9103),
+            Change::Removed(0x0002, 1),
+            Change::Added(0x0003, 2),
+        ];
+        assert_eq!(expected, compare(&old, &new));
+        assert!(compare(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_format() {
+        let old = MemoryImage::new("A", vec![(0x0010, 0x9102), (0x0011, 0xEF00)]);
+        let new = MemoryImage::new("B", vec![(0x0010, 0x9103), (0x0011, 0xEF00)]);
+        let changes = compare(&old, &new);
+        assert_eq!(
+            "- MM A
++ MM B
+- 0010  9102  L 1,*+2
++ 0010  9103  L 1,*+3
+1 changed, 0 added, 0 removed
+",
+            format(&old, &new, &changes, &[])
+        );
+
+        let symbols = vec![(0x0000, "START".to_string()), (0x0010, "LOOP".to_string())];
+        let new = MemoryImage::new("A", vec![(0x0010, 0x9102), (0x0012, 0xEF00)]);
+        let changes = compare(&old, &new);
+        assert_eq!(
+            "- 0011  EF00  HLT                     ; LOOP+1
++ 0012  EF00  HLT                     ; LOOP+2
+0 changed, 1 added, 1 removed
+",
+            format(&old, &new, &changes, &symbols)
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use crate::image::MemoryImage;
+use crate::instruction::{Instruction, MachineAddress, MachineCode};
+
+// Disassembles a word into an instruction, or DC if it is not an instruction
+pub fn disassemble_word(word: MachineCode) -> String {
+    Instruction::try_from(word)
+        .unwrap_or(Instruction::Dc {
+            value: word,
+            unresolved_symbol: None,
+        })
+        .to_string()
+}
+
+// Disassembles a memory image into a source program which assembles into the same image
+pub fn disassemble(image: &MemoryImage) -> String {
+    let mut program = format!("TITLE {}\n", image.title);
+
+    let mut next_address: MachineAddress = 0;
+    for (address, word) in &image.words {
+        if *address != next_address {
+            program += &format!("{:8}{}\n", "", Instruction::Org(*address));
+        }
+        program += &format!(
+            "{:8}{:<24}; {address:04X}  {word:04X}\n",
+            "",
+            disassemble_word(*word)
+        );
+        next_address = address.wrapping_add(1);
+    }
+
+    program + "        END\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::disassemble;
+    use crate::image::MemoryImage;
+
+    #[test]
+    fn test_disassemble() {
+        let image = MemoryImage::new(
+            "TEST",
+            vec![(0x0000, 0x9102), (0x0001, 0xEF00), (0x0010, 0x1234)],
+        );
+        let expected = "TITLE TEST
+        L 1,*+2                 ; 0000  9102
+        HLT                     ; 0001  EF00
+        ORG X\"10
+        SUB 2,52                ; 0010  1234
+        END
+";
+        assert_eq!(expected, disassemble(&image));
+    }
+}
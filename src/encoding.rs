@@ -0,0 +1,132 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, EUC_JP, SHIFT_JIS, UTF_8};
+
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+// Encoding of source programs given by `--input-encoding`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InputEncoding {
+    Auto,
+    Utf8,
+    ShiftJis,
+    EucJp,
+}
+
+impl FromStr for InputEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(InputEncoding::Auto),
+            "utf-8" => Ok(InputEncoding::Utf8),
+            "sjis" => Ok(InputEncoding::ShiftJis),
+            "euc-jp" => Ok(InputEncoding::EucJp),
+            _ => Err(format!("{s}: Unknown encoding")),
+        }
+    }
+}
+
+// Guesses the encoding, preferring UTF-8 when the bytes are valid UTF-8.
+// Programs are mostly ASCII with Japanese comments, so the guess leans towards Japanese.
+fn detect(bytes: &[u8]) -> &'static Encoding {
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(Some(b"jp"), false)
+}
+
+// Decodes a source program, returning the text and the encoding it was written in
+pub fn decode(
+    bytes: &[u8],
+    encoding: InputEncoding,
+) -> Result<(String, &'static Encoding), String> {
+    let encoding = match encoding {
+        InputEncoding::Auto => detect(bytes),
+        InputEncoding::Utf8 => UTF_8,
+        InputEncoding::ShiftJis => SHIFT_JIS,
+        InputEncoding::EucJp => EUC_JP,
+    };
+    // A byte order mark is not a part of the program
+    let bytes = match Encoding::for_bom(bytes) {
+        Some((bom_encoding, length)) if bom_encoding == encoding => &bytes[length..],
+        _ => bytes,
+    };
+    match encoding.decode_without_bom_handling_and_without_replacement(bytes) {
+        Some(text) => Ok((text.into_owned(), encoding)),
+        None => Err(format!("not a valid {} text", encoding.name())),
+    }
+}
+
+// Reads and decodes a source program, with the path in error messages
+pub fn read_file(
+    path: &Path,
+    encoding: InputEncoding,
+) -> Result<(String, &'static Encoding), String> {
+    let bytes = fs::read(path).map_err(|why| format!("{}: {why}", path.display()))?;
+    decode(&bytes, encoding).map_err(|why| format!("{}: {why}", path.display()))
+}
+
+// Encodes text into the encoding, for outputs which keep the encoding of the source
+pub fn encode(text: &str, encoding: &'static Encoding) -> Vec<u8> {
+    encoding.encode(text).0.into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, InputEncoding};
+    use encoding_rs::{EUC_JP, SHIFT_JIS, UTF_8};
+
+    // "TITLE T ; 合計" in each encoding
+    const UTF_8_SOURCE: &[u8] = b"TITLE T ; \xE5\x90\x88\xE8\xA8\x88\n";
+    const SHIFT_JIS_SOURCE: &[u8] = b"TITLE T ; \x8D\x87\x8C\x76\n";
+    const EUC_JP_SOURCE: &[u8] = b"TITLE T ; \xB9\xE7\xB7\xD7\n";
+
+    #[test]
+    fn test_decode() {
+        let text = "TITLE T ; 合計\n".to_string();
+        assert_eq!(
+            Ok((text.clone(), UTF_8)),
+            decode(UTF_8_SOURCE, InputEncoding::Utf8)
+        );
+        assert_eq!(
+            Ok((text.clone(), SHIFT_JIS)),
+            decode(SHIFT_JIS_SOURCE, InputEncoding::ShiftJis)
+        );
+        assert_eq!(
+            Ok((text.clone(), EUC_JP)),
+            decode(EUC_JP_SOURCE, InputEncoding::EucJp)
+        );
+        assert_eq!(
+            Ok(("TITLE T\n".to_string(), UTF_8)),
+            decode(b"\xEF\xBB\xBFTITLE T\n", InputEncoding::Utf8)
+        );
+        assert!(decode(SHIFT_JIS_SOURCE, InputEncoding::Utf8).is_err());
+    }
+
+    #[test]
+    fn test_decode_auto() {
+        let text = "TITLE T ; 合計\n".to_string();
+        assert_eq!(
+            Ok((text.clone(), UTF_8)),
+            decode(UTF_8_SOURCE, InputEncoding::Auto)
+        );
+        assert_eq!(
+            Ok((text.clone(), SHIFT_JIS)),
+            decode(SHIFT_JIS_SOURCE, InputEncoding::Auto)
+        );
+        assert_eq!(
+            Ok((text, EUC_JP)),
+            decode(EUC_JP_SOURCE, InputEncoding::Auto)
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(SHIFT_JIS_SOURCE, encode("TITLE T ; 合計\n", SHIFT_JIS));
+        assert_eq!(UTF_8_SOURCE, encode("TITLE T ; 合計\n", UTF_8));
+    }
+}
@@ -0,0 +1,131 @@
+use crate::lexer;
+use crate::token::Token;
+
+// Column where mnemonics start
+const INSTRUCTION_COLUMN: usize = 8;
+// Width of the mnemonic field
+const MNEMONIC_WIDTH: usize = 6;
+// Column where comments after an instruction start
+const COMMENT_COLUMN: usize = 32;
+
+// Formats a source program line by line, keeping comments and blank lines
+pub fn format(source: &str) -> String {
+    let mut formatted: String = source
+        .lines()
+        .map(format_line)
+        .collect::<Vec<String>>()
+        .join("\n");
+    if source.ends_with('\n') {
+        formatted.push('\n');
+    }
+    formatted
+}
+
+fn format_line(line: &str) -> String {
+    let tokens = lexer::tokenize(line);
+    // Lines which cannot be tokenized are left as they are
+    if tokens.is_empty() || tokens.iter().any(|(token, _)| *token == Token::Error) {
+        return line.trim_end().to_string();
+    }
+    let code_end = tokens.last().unwrap().1.end;
+    let comment = line[code_end..].trim();
+
+    let (label, statement) = match &tokens[..] {
+        [(Token::String(label), _), (Token::Colon, _), statement @ ..] => {
+            (Some(format!("{label}:")), statement)
+        }
+        // A register alias such as `SP REG 3` has its name in the label field
+        [(Token::String(name), _), statement @ ..] if matches!(statement.first(), Some((Token::String(reg), _)) if reg.eq_ignore_ascii_case("REG")) => {
+            (Some(name.to_string()), statement)
+        }
+        statement => (None, statement),
+    };
+
+    let mut formatted = match &label {
+        Some(label) => format!("{label:<width$}", width = INSTRUCTION_COLUMN - 1) + " ",
+        None => " ".repeat(INSTRUCTION_COLUMN),
+    };
+    if let Some(((_, mnemonic), operands)) = statement.split_first() {
+        formatted += &line[mnemonic.clone()];
+        if !operands.is_empty() {
+            let padding = MNEMONIC_WIDTH.saturating_sub(mnemonic.len()).max(1);
+            formatted += &" ".repeat(padding);
+            formatted += &format_operands(line, operands);
+        }
+    }
+    let mut formatted = formatted.trim_end().to_string();
+
+    if !comment.is_empty() {
+        let padding = COMMENT_COLUMN
+            .saturating_sub(formatted.chars().count())
+            .max(1);
+        formatted += &" ".repeat(padding);
+        formatted += comment;
+    }
+    formatted
+}
+
+// Joins operands without spaces, except between two words which would otherwise merge
+fn format_operands(line: &str, operands: &[(Token, logos::Span)]) -> String {
+    let is_word = |token: &Token| {
+        !matches!(
+            token,
+            Token::Comma
+                | Token::Lparen
+                | Token::Rparen
+                | Token::Plus
+                | Token::Minus
+                | Token::Star
+                | Token::Equal
+                | Token::Langle
+                | Token::Rangle
+        )
+    };
+
+    let mut formatted = String::new();
+    let mut previous: Option<&(Token, logos::Span)> = None;
+    for operand in operands {
+        if let Some((previous_token, previous_span)) = previous {
+            let separated = previous_span.end < operand.1.start;
+            if separated && is_word(previous_token) && is_word(&operand.0) {
+                formatted.push(' ');
+            }
+        }
+        formatted += &line[operand.1.clone()];
+        previous = Some(operand);
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format;
+
+    #[test]
+    fn test_format() {
+        let source = "TITLE  EX1\n; sum up\nLOOP:ADD 0 , 1 ( 2 ) ;add\n  BNZ   LOOP\nVERYLONGLABEL: HLT\n\nX: DC 'AB\n END";
+        let expected = "        TITLE EX1\n; sum up\nLOOP:   ADD   0,1(2)            ;add\n        BNZ   LOOP\nVERYLONGLABEL: HLT\n\nX:      DC    'AB\n        END";
+        assert_eq!(expected, format(source));
+    }
+
+    #[test]
+    fn test_format_is_idempotent() {
+        let source =
+            "        TITLE EX1\nLOOP:   L     1,LOOP+2          ; comment\nLABEL:\n        END\n";
+        assert_eq!(source, format(source));
+        assert_eq!(format(source), format(&format(source)));
+    }
+
+    #[test]
+    fn test_format_register_alias() {
+        let source = "SP REG 3\n  LEA SP , 1 ( SP )\n";
+        let expected = "SP      REG   3\n        LEA   SP,1(SP)\n";
+        assert_eq!(expected, format(source));
+    }
+
+    #[test]
+    fn test_format_keeps_invalid_lines() {
+        let source = "  LC 1,65536   \n";
+        assert_eq!("  LC 1,65536\n", format(source));
+    }
+}
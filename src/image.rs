@@ -0,0 +1,181 @@
+use crate::instruction::{MachineAddress, MachineCode};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+// A memory image in the `MM <title>` / `AAAA  CCCC` format, which simulators load
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct MemoryImage {
+    pub title: String,
+    // Words in the order they are written
+    pub words: Vec<(MachineAddress, MachineCode)>,
+}
+
+// An error in a memory image, at a 1-origin line
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ImageError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl MemoryImage {
+    pub fn new(title: &str, words: Vec<(MachineAddress, MachineCode)>) -> Self {
+        MemoryImage {
+            title: title.to_string(),
+            words,
+        }
+    }
+
+    // Word at the address, if any
+    pub fn get(&self, address: MachineAddress) -> Option<MachineCode> {
+        self.words
+            .iter()
+            .find(|(a, _)| *a == address)
+            .map(|(_, code)| *code)
+    }
+
+    // Writes the image without a trailing newline, as m1sim expects
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write!(w, "{self}")
+    }
+}
+
+impl fmt::Display for MemoryImage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MM {}", self.title)?;
+        for (a, c) in self.words.iter() {
+            write!(f, "\n{a:04X}  {c:04X}")?;
+        }
+        Ok(())
+    }
+}
+
+// Parses a field of exactly four hexadecimal digits
+fn parse_word(field: &str) -> Option<u16> {
+    if field.len() == 4 && field.chars().all(|c| c.is_ascii_hexdigit()) {
+        u16::from_str_radix(field, 16).ok()
+    } else {
+        None
+    }
+}
+
+impl FromStr for MemoryImage {
+    type Err = ImageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |line: usize, message: String| ImageError { line, message };
+
+        let mut lines = s.lines();
+        let title = lines
+            .next()
+            .and_then(|line| line.strip_prefix("MM "))
+            .ok_or_else(|| error(1, "the first line must be `MM <title>`".to_string()))?;
+        if title.is_empty() || title.contains(char::is_whitespace) {
+            return Err(error(1, format!("`{title}` is not a valid title")));
+        }
+
+        let mut words = Vec::new();
+        let mut defined_at = HashMap::new();
+        for (i, line) in lines.enumerate() {
+            let line_number = i + 2;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (address, code) = match fields[..] {
+                [address, code] => (address, code),
+                _ => return Err(error(line_number, "expected `AAAA  CCCC`".to_string())),
+            };
+            let address = parse_word(address).ok_or_else(|| {
+                error(
+                    line_number,
+                    format!("`{address}` is not a 4-digit hexadecimal address"),
+                )
+            })?;
+            let code = parse_word(code).ok_or_else(|| {
+                error(
+                    line_number,
+                    format!("`{code}` is not a 4-digit hexadecimal word"),
+                )
+            })?;
+            if let Some(defined) = defined_at.insert(address, line_number) {
+                return Err(error(
+                    line_number,
+                    format!("address {address:04X} is already written at line {defined}"),
+                ));
+            }
+            words.push((address, code));
+        }
+
+        Ok(MemoryImage {
+            title: title.to_string(),
+            words,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ImageError, MemoryImage};
+
+    #[test]
+    fn test_write() {
+        let image = MemoryImage::new("TEST", vec![(0x0000, 0x0604), (0x0001, 0xEF00)]);
+        let mut written = Vec::new();
+        image.write(&mut written).unwrap();
+        assert_eq!(b"MM TEST\n0000  0604\n0001  EF00".to_vec(), written);
+        assert_eq!("MM EMPTY", MemoryImage::new("EMPTY", vec![]).to_string());
+    }
+
+    #[test]
+    fn test_read() {
+        let expected = MemoryImage::new("TEST", vec![(0x0000, 0x0604), (0x0001, 0xEF00)]);
+        assert_eq!(
+            Ok(expected.clone()),
+            "MM TEST\n0000  0604\n0001  EF00".parse()
+        );
+        assert_eq!(
+            Ok(expected.clone()),
+            "MM TEST\n0000  0604\n0001  EF00\n".parse()
+        );
+        assert_eq!(Ok(expected.clone()), expected.to_string().parse());
+        assert_eq!(Some(0xEF00), expected.get(0x0001));
+        assert_eq!(None, expected.get(0x0002));
+    }
+
+    fn error(text: &str) -> ImageError {
+        text.parse::<MemoryImage>().unwrap_err()
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        assert_eq!(1, error("0000  0604").line);
+        assert_eq!(1, error("MM \n0000  0604").line);
+        assert_eq!(1, error("MM A B\n0000  0604").line);
+        assert_eq!(
+            "line 2: `XYZW` is not a 4-digit hexadecimal word",
+            error("MM TEST\n0000  XYZW").to_string()
+        );
+        assert_eq!(
+            "line 2: `00000` is not a 4-digit hexadecimal address",
+            error("MM TEST\n00000  0604").to_string()
+        );
+        assert_eq!(
+            "line 2: expected `AAAA  CCCC`",
+            error("MM TEST\n0000").to_string()
+        );
+        assert_eq!(
+            "line 3: expected `AAAA  CCCC`",
+            error("MM TEST\n0000  0604\n\n").to_string()
+        );
+        assert_eq!(
+            "line 4: address 0000 is already written at line 2",
+            error("MM TEST\n0000  0604\n0001  0604\n0000  EF00").to_string()
+        );
+    }
+}
@@ -0,0 +1,183 @@
+// Inclusion of binary files by `INCBIN "file"[,offset[,length]]`.
+// The words of a file follow its INCBIN line as DC, so addresses after it are counted like any
+// other data.
+
+use crate::diagnostic::Diagnostic;
+use crate::instruction::{Instruction, MachineCode};
+use crate::parser::ProgramLine;
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+// Order of the two bytes of each word read by INCBIN, given by `--byte-order`
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum ByteOrder {
+    #[default]
+    Big,
+    Little,
+}
+
+impl FromStr for ByteOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "big" => Ok(ByteOrder::Big),
+            "little" => Ok(ByteOrder::Little),
+            _ => Err(format!("{s}: Unknown byte order")),
+        }
+    }
+}
+
+impl fmt::Display for ByteOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ByteOrder::Big => write!(f, "big"),
+            ByteOrder::Little => write!(f, "little"),
+        }
+    }
+}
+
+// Packs bytes into words. A last odd byte is paired with zero.
+pub fn words(bytes: &[u8], byte_order: ByteOrder) -> Vec<MachineCode> {
+    bytes
+        .chunks(2)
+        .map(|pair| {
+            let pair = [pair[0], pair.get(1).copied().unwrap_or(0)];
+            match byte_order {
+                ByteOrder::Big => u16::from_be_bytes(pair),
+                ByteOrder::Little => u16::from_le_bytes(pair),
+            }
+        })
+        .collect()
+}
+
+// Reads `length` bytes from `offset`, or up to the end of the file
+fn read(path: &Path, offset: u16, length: Option<u16>) -> Result<Vec<u8>, String> {
+    let bytes = fs::read(path).map_err(|why| format!("{}: {why}", path.display()))?;
+    let offset = usize::from(offset);
+    let Some(rest) = bytes.get(offset..) else {
+        return Err(format!(
+            "offset {offset} is beyond the end of {}, which has {} bytes",
+            path.display(),
+            bytes.len()
+        ));
+    };
+    match length.map(usize::from) {
+        Some(length) if length > rest.len() => Err(format!(
+            "{} has only {} bytes after offset {offset}",
+            path.display(),
+            rest.len()
+        )),
+        Some(length) => Ok(rest[..length].to_vec()),
+        None => Ok(rest.to_vec()),
+    }
+}
+
+// Places the words of each INCBIN file after its line. Files are looked up from `directory`.
+pub fn include(
+    lines: &mut Vec<ProgramLine>,
+    directory: &Path,
+    byte_order: ByteOrder,
+) -> Vec<Diagnostic> {
+    let mut errors = Vec::new();
+    let mut included = Vec::new();
+    for line in lines.drain(..) {
+        let Some(Instruction::Incbin {
+            path,
+            offset,
+            length,
+        }) = line.instruction
+        else {
+            included.push(line);
+            continue;
+        };
+        match read(&directory.join(path), offset, length) {
+            Ok(bytes) => {
+                let span = line.span.clone();
+                included.push(line);
+                included.extend(
+                    words(&bytes, byte_order)
+                        .into_iter()
+                        .map(|value| ProgramLine {
+                            label: None,
+                            instruction: Some(Instruction::Dc {
+                                value,
+                                unresolved_symbol: None,
+                            }),
+                            span: span.clone(),
+                            symbol_span: None,
+                        }),
+                );
+            }
+            Err(why) => errors.push(Diagnostic::from_binary_file(line.span, why)),
+        }
+    }
+    *lines = included;
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{include, words, ByteOrder};
+    use crate::instruction::Instruction;
+    use crate::lexer;
+    use crate::parser::{self, Syntax};
+
+    use std::fs;
+
+    #[test]
+    fn test_words() {
+        assert_eq!(
+            vec![0x1234, 0x5600],
+            words(&[0x12, 0x34, 0x56], ByteOrder::Big)
+        );
+        assert_eq!(
+            vec![0x3412, 0x0056],
+            words(&[0x12, 0x34, 0x56], ByteOrder::Little)
+        );
+        assert!(words(&[], ByteOrder::Big).is_empty());
+    }
+
+    #[test]
+    fn test_include() {
+        let directory = std::env::temp_dir().join(format!("rm1asm-incbin-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("a.bin"), [1, 2, 3, 4, 5, 6]).unwrap();
+
+        let source =
+            "TITLE T\nT: INCBIN \"a.bin\",1,4\n  INCBIN \"a.bin\",7\n  INCBIN \"b.bin\"\nEND\n";
+        let mut lines = parser::parse(lexer::tokenize(source), Syntax::default())
+            .0
+            .unwrap()
+            .lines;
+        let errors = include(&mut lines, &directory, ByteOrder::Big);
+        assert_eq!(3, lines.len());
+        assert_eq!(Some("T"), lines[0].label);
+        assert_eq!(
+            vec![
+                Some(Instruction::Dc {
+                    value: 0x0203,
+                    unresolved_symbol: None,
+                }),
+                Some(Instruction::Dc {
+                    value: 0x0405,
+                    unresolved_symbol: None,
+                }),
+            ],
+            lines[1..]
+                .iter()
+                .map(|line| line.instruction.clone())
+                .collect::<Vec<_>>()
+        );
+        let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+        assert_eq!(2, messages.len());
+        assert!(messages[0].starts_with("offset 7 is beyond the end of "));
+        assert!(messages[0].ends_with("a.bin, which has 6 bytes"));
+        assert!(messages[1].contains("b.bin: "));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
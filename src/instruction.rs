@@ -1,3 +1,5 @@
+use std::fmt;
+
 pub type MachineCode = u16;
 pub type MachineAddress = u16;
 
@@ -5,6 +7,8 @@ pub type MachineAddress = u16;
 pub enum Address<'a> {
     Constant(MachineAddress),
     Unresolved { symbol_name: &'a str, offset: i16 },
+    // `=X"1234"`, a word in the next literal pool
+    Literal(MachineCode),
 }
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -26,6 +30,20 @@ impl From<&Register> for MachineCode {
     }
 }
 
+impl TryFrom<MachineCode> for Register {
+    type Error = ();
+
+    fn try_from(number: MachineCode) -> Result<Self, Self::Error> {
+        match number {
+            0 => Ok(Register::Zero),
+            1 => Ok(Register::One),
+            2 => Ok(Register::Two),
+            3 => Ok(Register::Three),
+            _ => Err(()),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Opecode1 {
     Add,
@@ -267,8 +285,33 @@ pub enum Instruction<'a> {
         value: MachineCode,
         unresolved_symbol: Option<&'a str>,
     },
-    Ds(u16),
+    // Size and the value filling the area, which DS and FILL define
+    Ds(u16, MachineCode),
+    // Zeros up to the next multiple of the alignment
+    Align(u16),
+    // Addresses without words in the image
+    Reserve(u16),
     Org(MachineAddress),
+    // Places the literals used since the previous pool
+    Ltorg,
+    // A binary file, whose words follow as DC
+    Incbin {
+        path: &'a str,
+        offset: u16,
+        length: Option<u16>,
+    },
+}
+
+impl Instruction<'_> {
+    // Number of words which the instruction occupies at `address`
+    pub fn size(&self, address: MachineAddress) -> u16 {
+        match self {
+            Instruction::Org(_) | Instruction::Ltorg | Instruction::Incbin { .. } => 0,
+            Instruction::Ds(size, _) | Instruction::Reserve(size) => *size,
+            Instruction::Align(alignment) => (alignment - address % alignment) % alignment,
+            _ => 1,
+        }
+    }
 }
 
 impl TryFrom<&Instruction<'_>> for MachineCode {
@@ -352,6 +395,359 @@ impl TryFrom<&Instruction<'_>> for MachineCode {
     }
 }
 
+impl fmt::Display for Address<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Address::Constant(c) => match *c as u8 as i8 {
+                0 => write!(f, "*"),
+                d if d > 0 => write!(f, "*+{d}"),
+                d => write!(f, "*{d}"),
+            },
+            Address::Unresolved {
+                symbol_name,
+                offset,
+            } => match offset {
+                0 => write!(f, "{symbol_name}"),
+                o if *o > 0 => write!(f, "{symbol_name}+{o}"),
+                o => write!(f, "{symbol_name}{o}"),
+            },
+            Address::Literal(value) => write!(f, "=X\"{value:X}"),
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", MachineCode::from(self))
+    }
+}
+
+impl fmt::Display for Opecode1 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Opecode1::Add => "ADD",
+            Opecode1::Sub => "SUB",
+            Opecode1::And => "AND",
+            Opecode1::Or => "OR",
+            Opecode1::Xor => "XOR",
+            Opecode1::Mult => "MULT",
+            Opecode1::Div => "DIV",
+            Opecode1::Cmp => "CMP",
+            Opecode1::Ex => "EX",
+        })
+    }
+}
+
+impl fmt::Display for Opecode2 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Opecode2::Lc => "LC",
+            Opecode2::Push => "PUSH",
+            Opecode2::Pop => "POP",
+        })
+    }
+}
+
+impl fmt::Display for Opecode3 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Opecode3::Sl => "SL",
+            Opecode3::Sa => "SA",
+            Opecode3::Sc => "SC",
+            Opecode3::Bix => "BIX",
+        })
+    }
+}
+
+impl fmt::Display for Opecode4 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Opecode4::Lea => "LEA",
+            Opecode4::Lx => "LX",
+            Opecode4::Stx => "STX",
+        })
+    }
+}
+
+impl fmt::Display for Opecode5 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Opecode5::L => "L",
+            Opecode5::St => "ST",
+            Opecode5::La => "LA",
+        })
+    }
+}
+
+impl fmt::Display for Opecode6 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Opecode6::Bdis => "BDIS",
+            Opecode6::Bp => "BP",
+            Opecode6::Bz => "BZ",
+            Opecode6::Bm => "BM",
+            Opecode6::Bc => "BC",
+            Opecode6::Bnp => "BNP",
+            Opecode6::Bnz => "BNZ",
+            Opecode6::Bnm => "BNM",
+            Opecode6::Bnc => "BNC",
+            Opecode6::B => "B",
+            Opecode6::Bi => "BI",
+            Opecode6::Bsr => "BSR",
+        })
+    }
+}
+
+impl fmt::Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Device::Cr => "CR",
+            Device::Lpt => "LPT",
+        })
+    }
+}
+
+impl fmt::Display for Opecode7 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Opecode7::Rio => "RIO",
+            Opecode7::Wio => "WIO",
+        })
+    }
+}
+
+impl fmt::Display for Opecode8 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Opecode8::Ret => "RET",
+            Opecode8::Nop => "NOP",
+            Opecode8::Hlt => "HLT",
+        })
+    }
+}
+
+// Prints the instruction in the source syntax accepted by the parser
+impl fmt::Display for Instruction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::Group1 {
+                op,
+                ra: Register::Zero,
+                rb,
+                constant,
+            } => write!(f, "{op} {rb},{constant}"),
+            Instruction::Group1 {
+                op,
+                ra,
+                rb,
+                constant: 0,
+            } => write!(f, "{op} {rb},({ra})"),
+            Instruction::Group1 {
+                op,
+                ra,
+                rb,
+                constant,
+            } => write!(f, "{op} {rb},{constant}({ra})"),
+            Instruction::Group2 { op, rb, constant } => write!(f, "{op} {rb},{constant}"),
+            Instruction::Group3 { op, rb, constant } => write!(f, "{op} {rb},{constant}"),
+            Instruction::Group4 {
+                op,
+                ra,
+                rb,
+                constant: 0,
+            } => write!(f, "{op} {rb},({ra})"),
+            Instruction::Group4 {
+                op,
+                ra,
+                rb,
+                constant,
+            } => write!(f, "{op} {rb},{constant}({ra})"),
+            Instruction::Group5 { op, rb, address } => write!(f, "{op} {rb},{address}"),
+            Instruction::Group6 { op, address } => write!(f, "{op} {address}"),
+            Instruction::Group7 { op, device } => write!(f, "{op} {device}"),
+            Instruction::Group8 { op } => write!(f, "{op}"),
+            // The value of a word holding a symbol is the offset from it
+            Instruction::Dc {
+                value,
+                unresolved_symbol: Some(symbol_name),
+            } => {
+                let address = Address::Unresolved {
+                    symbol_name,
+                    offset: *value as i16,
+                };
+                write!(f, "DC {address}")
+            }
+            Instruction::Dc { value, .. } => write!(f, "DC X\"{value:04X}"),
+            Instruction::Ds(size, 0) => write!(f, "DS {size}"),
+            Instruction::Ds(size, value) => write!(f, "DS {size},X\"{value:04X}"),
+            Instruction::Align(alignment) => write!(f, "ALIGN {alignment}"),
+            Instruction::Reserve(size) => write!(f, "RESERVE {size}"),
+            Instruction::Org(address) => write!(f, "ORG X\"{address:X}"),
+            Instruction::Ltorg => write!(f, "LTORG"),
+            Instruction::Incbin {
+                path,
+                offset,
+                length: None,
+            } => write!(f, "INCBIN \"{path}\",{offset}"),
+            Instruction::Incbin {
+                path,
+                offset,
+                length: Some(length),
+            } => write!(f, "INCBIN \"{path}\",{offset},{length}"),
+        }
+    }
+}
+
+impl TryFrom<MachineCode> for Instruction<'_> {
+    type Error = ();
+
+    // Decodes a machine code word. Words which are not valid instructions are errors.
+    fn try_from(code: MachineCode) -> Result<Self, Self::Error> {
+        let register = |shift: u16| match (code >> shift) & 3 {
+            0 => Register::Zero,
+            1 => Register::One,
+            2 => Register::Two,
+            _ => Register::Three,
+        };
+        let ra = register(10);
+        let rb = register(8);
+        let constant = (code & 0xFF) as u8;
+        let address = Address::Constant(code & 0xFF);
+
+        let op1 = match code >> 12 {
+            0x0 => Some(Opecode1::Add),
+            0x1 => Some(Opecode1::Sub),
+            0x2 => Some(Opecode1::And),
+            0x3 => Some(Opecode1::Or),
+            0x4 => Some(Opecode1::Xor),
+            0x6 => Some(Opecode1::Mult),
+            0x7 => Some(Opecode1::Div),
+            0x8 => Some(Opecode1::Cmp),
+            0xF => Some(Opecode1::Ex),
+            _ => None,
+        };
+        if let Some(op) = op1 {
+            return Ok(Instruction::Group1 {
+                op,
+                ra,
+                rb,
+                constant,
+            });
+        }
+
+        let op4 = match code >> 12 {
+            0xA => Some(Opecode4::Lea),
+            0xB => Some(Opecode4::Lx),
+            0xC => Some(Opecode4::Stx),
+            _ => None,
+        };
+        if let Some(op) = op4 {
+            return Ok(Instruction::Group4 {
+                op,
+                ra,
+                rb,
+                constant: constant as i8,
+            });
+        }
+
+        match code >> 10 {
+            0x14 => Ok(Instruction::Group3 {
+                op: Opecode3::Sl,
+                rb,
+                constant: constant as i8,
+            }),
+            0x15 => Ok(Instruction::Group3 {
+                op: Opecode3::Sa,
+                rb,
+                constant: constant as i8,
+            }),
+            0x16 => Ok(Instruction::Group3 {
+                op: Opecode3::Sc,
+                rb,
+                constant: constant as i8,
+            }),
+            0x24 => Ok(Instruction::Group5 {
+                op: Opecode5::L,
+                rb,
+                address,
+            }),
+            0x25 => Ok(Instruction::Group5 {
+                op: Opecode5::St,
+                rb,
+                address,
+            }),
+            0x26 => Ok(Instruction::Group5 {
+                op: Opecode5::La,
+                rb,
+                address,
+            }),
+            0x27 => Ok(Instruction::Group2 {
+                op: Opecode2::Lc,
+                rb,
+                constant,
+            }),
+            0x34 => Ok(Instruction::Group2 {
+                op: Opecode2::Push,
+                rb,
+                constant,
+            }),
+            0x35 => Ok(Instruction::Group2 {
+                op: Opecode2::Pop,
+                rb,
+                constant,
+            }),
+            0x36 => Ok(Instruction::Group3 {
+                op: Opecode3::Bix,
+                rb,
+                constant: constant as i8,
+            }),
+            _ => {
+                let op6 = match code >> 8 {
+                    0xDC => Some(Opecode6::Bdis),
+                    0xE0 => Some(Opecode6::Bp),
+                    0xE1 => Some(Opecode6::Bz),
+                    0xE2 => Some(Opecode6::Bm),
+                    0xE3 => Some(Opecode6::Bc),
+                    0xE4 => Some(Opecode6::Bnp),
+                    0xE5 => Some(Opecode6::Bnz),
+                    0xE6 => Some(Opecode6::Bnm),
+                    0xE7 => Some(Opecode6::Bnc),
+                    0xE8 => Some(Opecode6::B),
+                    0xE9 => Some(Opecode6::Bi),
+                    0xEA => Some(Opecode6::Bsr),
+                    _ => None,
+                };
+                if let Some(op) = op6 {
+                    return Ok(Instruction::Group6 { op, address });
+                }
+
+                match code {
+                    0xEC00 => Ok(Instruction::Group7 {
+                        op: Opecode7::Rio,
+                        device: Device::Cr,
+                    }),
+                    0xEC01 => Ok(Instruction::Group7 {
+                        op: Opecode7::Rio,
+                        device: Device::Lpt,
+                    }),
+                    0xED00 => Ok(Instruction::Group7 {
+                        op: Opecode7::Wio,
+                        device: Device::Cr,
+                    }),
+                    0xED01 => Ok(Instruction::Group7 {
+                        op: Opecode7::Wio,
+                        device: Device::Lpt,
+                    }),
+                    0xEB00 => Ok(Instruction::Group8 { op: Opecode8::Ret }),
+                    0xEE00 => Ok(Instruction::Group8 { op: Opecode8::Nop }),
+                    0xEF00 => Ok(Instruction::Group8 { op: Opecode8::Hlt }),
+                    _ => Err(()),
+                }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -821,4 +1217,58 @@ mod tests {
         let actual = MachineCode::try_from(&input);
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn test_decode() {
+        let codes: [MachineCode; 12] = [
+            0x0604, 0x9E45, 0x56FE, 0xD804, 0xBDFF, 0x96FB, 0xDC00, 0xE1FD, 0xED01, 0xEB00, 0xEE00,
+            0xEF00,
+        ];
+        for code in codes {
+            let instruction = Instruction::try_from(code).unwrap();
+            assert_eq!(Ok(code), MachineCode::try_from(&instruction));
+        }
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        for code in [0x5C00, 0xDD00, 0xEB01, 0xEC02, 0xEF10] {
+            assert_eq!(Err(()), Instruction::try_from(code));
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let cases = [
+            (0x0604, "ADD 2,4(1)"),
+            (0x0204, "ADD 2,4"),
+            (0x0600, "ADD 2,(1)"),
+            (0x9E45, "LC 2,69"),
+            (0x56FE, "SA 2,-2"),
+            (0xA900, "LEA 1,(2)"),
+            (0xBDFF, "LX 1,-1(3)"),
+            (0x96FB, "ST 2,*-5"),
+            (0x9100, "L 1,*"),
+            (0xE809, "B *+9"),
+            (0xED01, "WIO LPT"),
+            (0xEF00, "HLT"),
+        ];
+        for (code, text) in cases {
+            assert_eq!(text, Instruction::try_from(code).unwrap().to_string());
+        }
+        let input = Instruction::Group6 {
+            op: Opecode6::Bz,
+            address: Address::Unresolved {
+                symbol_name: "LOOP",
+                offset: -2,
+            },
+        };
+        assert_eq!("BZ LOOP-2", input.to_string());
+        let input = Instruction::Dc {
+            value: 0x1234,
+            unresolved_symbol: None,
+        };
+        assert_eq!("DC X\"1234", input.to_string());
+        assert_eq!("ORG X\"A00", Instruction::Org(0xA00).to_string());
+    }
 }
@@ -1,14 +1,33 @@
+use crate::dialect::Dialect;
 use crate::token::Token;
 use logos::{Logos, Span};
 
-pub fn tokenize(input: &str) -> Vec<(Token, Span)> {
+pub fn tokenize(input: &str) -> Vec<(Token<'_>, Span)> {
     Token::lexer(input).spanned().collect()
 }
 
+// The classic dialect reads `'A'` as `'A` followed by a quote, as the textbook does
+pub fn tokenize_in(input: &str, dialect: Dialect) -> Vec<(Token<'_>, Span)> {
+    let tokens = tokenize(input);
+    if dialect != Dialect::Classic {
+        return tokens;
+    }
+    tokens
+        .into_iter()
+        .map(|(token, span)| match token {
+            Token::Char(_) if input[span.clone()].chars().count() == 3 => {
+                (Token::Chars(&input[span.start + 1..span.end]), span)
+            }
+            token => (token, span),
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::tokenize;
     use super::Token;
+    use super::{tokenize, tokenize_in};
+    use crate::dialect::Dialect;
 
     #[test]
     fn test_string() {
@@ -109,6 +128,20 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn test_closed_numbers() {
+        let input = "X\"1F\" O\"17\" B\"101\" X\"1F \"A\"";
+        let expected = vec![
+            (Token::Hexadecimal(0x1F), 0..5),
+            (Token::Octal(0o17), 6..11),
+            (Token::Binary(0b101), 12..18),
+            (Token::Hexadecimal(0x1F), 19..23),
+            (Token::Text("A"), 24..27),
+        ];
+        let actual = tokenize(input);
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn test_octal() {
         let input = "O\"0123 O\"4567 O\"01234567";
@@ -133,6 +166,26 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn test_prefixed_numbers() {
+        let input = "0x1F 0X1f_00 $FF 1FH 0ffh 0o17 0O1_7 0b101 0B1_01 1_000 0x10000";
+        let expected = vec![
+            (Token::Hexadecimal(0x1F), 0..4),
+            (Token::Hexadecimal(0x1F00), 5..12),
+            (Token::Hexadecimal(0xFF), 13..16),
+            (Token::Hexadecimal(0x1F), 17..20),
+            (Token::Hexadecimal(0xFF), 21..25),
+            (Token::Octal(0o17), 26..30),
+            (Token::Octal(0o17), 31..36),
+            (Token::Binary(0b101), 37..42),
+            (Token::Binary(0b101), 43..49),
+            (Token::Decimal(1000), 50..55),
+            (Token::Error, 56..63),
+        ];
+        let actual = tokenize(input);
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn test_lparen() {
         let input = "( (    (";
@@ -225,6 +278,49 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn test_char() {
+        let input = r"'A' 'AB' '\n' '\'' '\x41\x42' ' ' '合' '合A";
+        let expected = vec![
+            (Token::Char("A"), 0..3),
+            (Token::Char("AB"), 4..8),
+            (Token::Char(r"\n"), 9..13),
+            (Token::Char(r"\'"), 14..18),
+            (Token::Char(r"\x41\x42"), 19..29),
+            (Token::Char(" "), 30..33),
+            (Token::Char("合"), 34..39),
+            (Token::Chars("合A"), 40..45),
+        ];
+        let actual = tokenize(input);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_tokenize_in_classic() {
+        let input = r"' ' 'A' 'AB '\n'";
+        let expected = vec![
+            (Token::Chars(" '"), 0..3),
+            (Token::Chars("A'"), 4..7),
+            (Token::Chars("AB"), 8..11),
+            (Token::Char(r"\n"), 12..16),
+        ];
+        assert_eq!(expected, tokenize_in(input, Dialect::Classic));
+        assert_eq!(Token::Char("A"), tokenize_in(input, Dialect::Extended)[1].0);
+    }
+
+    #[test]
+    fn test_text() {
+        let input = r#""Hello, world" "" "a;b\"\\" "open"#;
+        let expected = vec![
+            (Token::Text("Hello, world"), 0..14),
+            (Token::Text(""), 15..17),
+            (Token::Text(r#"a;b\"\\"#), 18..27),
+            (Token::Error, 28..33),
+        ];
+        let actual = tokenize(input);
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn test_plus() {
         let input = "+ +    +";
@@ -273,6 +369,18 @@ mod tests {
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn test_equal() {
+        let input = "= =X\"1";
+        let expected = vec![
+            (Token::Equal, 0..1),
+            (Token::Equal, 2..3),
+            (Token::Hexadecimal(1), 3..6),
+        ];
+        let actual = tokenize(input);
+        assert_eq!(expected, actual);
+    }
+
     #[test]
     fn test_eol() {
         let input = "\n \n    \n";
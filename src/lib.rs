@@ -0,0 +1,22 @@
+pub mod assembler;
+pub mod batch;
+pub mod codegen;
+pub mod corpus;
+pub mod debuginfo;
+pub mod diagnostic;
+pub mod dialect;
+pub mod diff;
+pub mod disasm;
+pub mod encoding;
+pub mod format;
+pub mod image;
+pub mod incbin;
+pub mod instruction;
+pub mod lexer;
+pub mod listing;
+pub mod map;
+pub mod parser;
+pub mod repeat;
+pub mod symbol;
+pub mod token;
+pub mod xref;
@@ -0,0 +1,147 @@
+// Assembly listing, which shows the address and the words of every source line.
+// Pseudo-instructions and relaxed branches are followed by the instructions which they expand
+// into.
+
+use crate::assembler::Program;
+use crate::codegen;
+use crate::diagnostic::line_column;
+use crate::instruction::{Instruction, MachineAddress, MachineCode};
+use crate::lexer;
+use crate::parser::{ProgramLine, PSEUDO_INSTRUCTIONS};
+use crate::token::Token;
+
+use std::collections::BTreeMap;
+
+// Column where expanded instructions start, under the mnemonics of a formatted program
+const INSTRUCTION_COLUMN: usize = 8;
+// Column of notes after expanded instructions
+const COMMENT_COLUMN: usize = 32;
+
+fn is_pseudo_instruction(line: &ProgramLine, source: &str) -> bool {
+    match lexer::tokenize(&source[line.instruction_span(source)]).first() {
+        Some((Token::String(mnemonic), _)) => PSEUDO_INSTRUCTIONS
+            .iter()
+            .any(|pseudo| pseudo.eq_ignore_ascii_case(mnemonic)),
+        _ => false,
+    }
+}
+
+fn row(word: Option<(MachineAddress, MachineCode)>, line: Option<usize>, text: &str) -> String {
+    let (address, code) = match word {
+        Some((address, code)) => (format!("{address:04X}"), format!("{code:04X}")),
+        None => (String::new(), String::new()),
+    };
+    let line = line.map_or(String::new(), |line| line.to_string());
+    format!("{address:4}  {code:4}  {line:>5}  {text}")
+        .trim_end()
+        .to_string()
+        + "\n"
+}
+
+pub fn format(program: &Program, source: &str) -> String {
+    let lines = &program.ast.lines;
+    // Words of each source line, by 1-origin line number
+    let mut words: BTreeMap<usize, Vec<(usize, MachineAddress, MachineCode)>> = BTreeMap::new();
+    for (i, address, code) in codegen::generate_lines(lines) {
+        let line_number = line_column(source, lines[i].span.start).0;
+        words
+            .entry(line_number)
+            .or_default()
+            .push((i, address, code));
+    }
+
+    let mut listing = "ADDR  CODE   LINE  SOURCE\n".to_string();
+    for (n, text) in source.lines().enumerate() {
+        let line_number = n + 1;
+        let Some(words) = words.get(&line_number) else {
+            listing += &row(None, Some(line_number), text);
+            continue;
+        };
+        let (first, _, _) = words[0];
+        let relaxed = program.relaxed.contains(&lines[first].span);
+        if relaxed || is_pseudo_instruction(&lines[first], source) {
+            listing += &row(None, Some(line_number), text);
+            for (j, (i, address, code)) in words.iter().enumerate() {
+                let instruction = lines[*i].instruction.as_ref().unwrap();
+                let mut expansion = format!("{:INSTRUCTION_COLUMN$}{instruction}", "");
+                if relaxed && j == 0 {
+                    expansion =
+                        format!("{expansion:COMMENT_COLUMN$}; relaxed, the target is out of range");
+                }
+                listing += &row(Some((*address, *code)), None, &expansion);
+            }
+        } else if let Some(Instruction::Ds(_, _) | Instruction::Align(_)) = lines[first].instruction
+        {
+            // A reserved area is shown by its first word
+            let (_, address, code) = words[0];
+            listing += &row(Some((address, code)), Some(line_number), text);
+        } else {
+            for (j, (_, address, code)) in words.iter().enumerate() {
+                let (line, text) = if j == 0 {
+                    (Some(line_number), text)
+                } else {
+                    (None, "")
+                };
+                listing += &row(Some((*address, *code)), line, text);
+            }
+        }
+    }
+    listing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format;
+    use crate::assembler::analyze;
+    use crate::parser::Syntax;
+
+    #[test]
+    fn test_format() {
+        let source = "        TITLE T
+; start
+        LI    1,0x1234
+        DC    1,2
+        JMP   DONE
+DONE:   DS    3
+        HLT
+        END
+";
+        let program = analyze(source, Syntax::default()).unwrap();
+        let expected = "ADDR  CODE   LINE  SOURCE
+                1          TITLE T
+                2  ; start
+                3          LI    1,0x1234
+0000  9D12                 LC 1,18
+0001  5108                 SL 1,8
+0002  3134                 OR 1,52
+0003  0001      4          DC    1,2
+0004  0002
+                5          JMP   DONE
+0005  E901                 BI *+1
+0006  0007                 DC X\"0007
+0007  0000      6  DONE:   DS    3
+000A  EF00      7          HLT
+                8          END
+";
+        assert_eq!(expected, format(&program, source));
+    }
+
+    #[test]
+    fn test_format_relaxed() {
+        let source = "        TITLE T\nLOOP:   DS    200\n        B     LOOP\n        END\n";
+        let syntax = Syntax {
+            relax_branches: true,
+            ..Syntax::default()
+        };
+        let program = analyze(source, syntax).unwrap();
+        let expected = "ADDR  CODE   LINE  SOURCE
+                1          TITLE T
+0000  0000      2  LOOP:   DS    200
+                3          B     LOOP
+00C8  E901                 BI *+1                  ; relaxed, the target is out of range
+00C9  0000                 DC X\"0000
+                4          END
+";
+        assert_eq!(expected, format(&program, source));
+    }
+}
@@ -1,92 +1,376 @@
-mod codegen;
-mod instruction;
-mod lexer;
-mod parser;
-mod symbol;
-mod token;
+mod cli;
 
-use ariadne::{Color, Fmt, Label, Report, ReportKind, Source};
-use clap::{App, Arg};
+use clap::ArgMatches;
+use encoding_rs::Encoding;
+use rm1asm::assembler::{self, Program};
+use rm1asm::diagnostic::{self, ErrorFormat};
+use rm1asm::encoding::{self, InputEncoding};
+use rm1asm::image::MemoryImage;
+use rm1asm::instruction::MachineAddress;
+use rm1asm::parser::Syntax;
+use rm1asm::{batch, codegen, corpus, debuginfo, diff, disasm, format, listing, map, symbol, xref};
 
 use std::fs::File;
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
 fn main() {
     // Create the argument parser
-    let matches = App::new(env!("CARGO_PKG_NAME"))
-        .version(env!("CARGO_PKG_VERSION"))
-        .about("MICRO-1 machine language assembler written in Rust")
-        .arg(Arg::with_name("input").help("source code").required(true))
-        .arg(
-            Arg::with_name("output")
-                .help("Sets output path")
-                .short("o")
-                .long("output")
-                .takes_value(true),
-        )
-        .get_matches();
+    let matches =
+        cli::build_cli().get_matches_from(cli::normalize_args(std::env::args().collect()));
 
-    // Read source program
-    let input_path = matches.value_of("input").unwrap();
-    let mut input_file =
-        File::open(input_path).unwrap_or_else(|_| panic!("{}: No such file", input_path));
-    let mut source_program = String::new();
-    input_file
-        .read_to_string(&mut source_program)
-        .unwrap_or_else(|_| panic!("{}: No such file", input_path));
-
-    // lexical analysis
-    let tokens = lexer::tokenize(&source_program);
-
-    // syntactic analysis
-    let (ast, errs) = parser::parse(tokens);
-
-    if errs.is_empty() {
-        let mut ast = ast.unwrap();
-        // symbol resolution
-        symbol::resolve_symbols(&mut ast.lines);
-        let unresolved_symbols = symbol::check_unresolve_symbols(&ast.lines);
-        if !unresolved_symbols.is_empty() {
-            eprintln!("Unresolved symbols found");
-            for unresolved_symbol in unresolved_symbols {
-                eprintln!("- {unresolved_symbol}");
-            }
+    match matches.subcommand() {
+        ("build", Some(matches)) => build(matches),
+        ("check", Some(matches)) => check(matches),
+        ("disasm", Some(matches)) => disasm(matches),
+        ("diff", Some(matches)) => diff(matches),
+        ("run", Some(matches)) => run(matches),
+        ("fmt", Some(matches)) => fmt(matches),
+        ("symbols", Some(matches)) => symbols(matches),
+        ("xref", Some(matches)) => xref(matches),
+        ("test-corpus", Some(matches)) => test_corpus(matches),
+        ("completions", Some(matches)) => {
+            let shell = matches.value_of("shell").unwrap().parse().unwrap();
+            cli::build_cli().gen_completions_to(
+                env!("CARGO_PKG_NAME"),
+                shell,
+                &mut std::io::stdout(),
+            );
+        }
+        _ => unreachable!(),
+    }
+}
+
+// `-` stands for the standard input or output
+const STDIO: &str = "-";
+
+// Reads and decodes a file, or prints the reason and exits
+fn read_file(path: &str, input_encoding: InputEncoding) -> (String, &'static Encoding) {
+    let mut bytes = Vec::new();
+    let read = if path == STDIO {
+        io::stdin().read_to_end(&mut bytes).map(|_| ())
+    } else {
+        std::fs::read(path).map(|read| bytes = read)
+    };
+    read.map_err(|why| why.to_string())
+        .and_then(|_| encoding::decode(&bytes, input_encoding))
+        .unwrap_or_else(|why| {
+            eprintln!("{}: {why}", display_name(path));
+            std::process::exit(1);
+        })
+}
+
+// Reads a source program in the encoding given by `--input-encoding`
+fn read_source(matches: &ArgMatches, path: &str) -> (String, &'static Encoding) {
+    let input_encoding = matches.value_of("input-encoding").unwrap().parse().unwrap();
+    read_file(path, input_encoding)
+}
+
+// Name of the input in diagnostics
+fn display_name(path: &str) -> &str {
+    if path == STDIO {
+        "<stdin>"
+    } else {
+        path
+    }
+}
+
+fn syntax(matches: &ArgMatches) -> Syntax {
+    Syntax {
+        dialect: matches.value_of("dialect").unwrap().parse().unwrap(),
+        decimal_org: matches.is_present("decimal-org"),
+        case_sensitive: matches.is_present("case-sensitive"),
+        relax_branches: matches.is_present("relax-branches"),
+        byte_order: matches.value_of("byte-order").unwrap().parse().unwrap(),
+    }
+}
+
+// Parses and resolves the program, or prints diagnostics and exits
+fn analyze<'a>(matches: &ArgMatches, input_path: &str, source_program: &'a str) -> Program<'a> {
+    let error_format: ErrorFormat = matches.value_of("error-format").unwrap().parse().unwrap();
+    let input_name = display_name(input_path);
+    // INCBIN files are looked up next to the source program
+    let directory = match Path::new(input_path).parent() {
+        Some(directory) if input_path != STDIO => directory,
+        _ => Path::new(""),
+    };
+    match assembler::analyze_in(source_program, syntax(matches), directory) {
+        Ok(program) => {
+            diagnostic::print(&program.warnings, error_format, input_name, source_program);
+            program
+        }
+        Err(diagnostics) => {
+            diagnostic::print(&diagnostics, error_format, input_name, source_program);
             std::process::exit(1);
         }
+    }
+}
 
-        // Set a binary file name
-        let output_path = if let Some(output_file_name) = matches.value_of("output") {
-            PathBuf::from(output_file_name)
+fn write_image(output_path: &Path, image: &MemoryImage) {
+    // Open a binary file
+    let mut file: Box<dyn Write> = if output_path == Path::new(STDIO) {
+        Box::new(io::stdout())
+    } else {
+        match File::create(output_path) {
+            Err(why) => panic!("{}: {why}", output_path.display()),
+            Ok(file) => Box::new(file),
+        }
+    };
+
+    // Write a binary file
+    image.write(&mut file).unwrap();
+}
+
+fn build(matches: &ArgMatches) {
+    let inputs: Vec<&str> = matches.values_of("input").unwrap().collect();
+    if inputs.len() > 1 || batch::is_pattern(inputs[0]) {
+        return build_all(matches, &inputs);
+    }
+
+    let input_path = inputs[0];
+    let (source_program, _) = read_source(matches, input_path);
+    let program = analyze(matches, input_path, &source_program);
+
+    // Set a binary file name
+    let output_path = if let Some(output_file_name) = matches.value_of("output") {
+        PathBuf::from(output_file_name)
+    } else if input_path == STDIO && !matches.is_present("out-dir") {
+        PathBuf::from(STDIO)
+    } else {
+        let input_path = if input_path == STDIO {
+            "stdin"
         } else {
-            let mut output_path = PathBuf::from(input_path);
-            output_path.set_extension("b");
-            output_path
+            input_path
         };
+        batch::output_path(
+            Path::new(input_path),
+            matches.value_of("out-dir").map(Path::new),
+        )
+    };
 
-        // Open a binary file
-        let mut file = match File::create(&output_path) {
-            Err(why) => panic!("{}: {why}", output_path.display()),
-            Ok(file) => file,
-        };
+    // Outputs sharing the standard output would be mixed into each other
+    let mut stdout_users: Vec<String> = ["listing", "map", "debug-info"]
+        .into_iter()
+        .filter(|name| matches.value_of(name) == Some(STDIO))
+        .map(|name| format!("--{name}"))
+        .collect();
+    if output_path == Path::new(STDIO) {
+        stdout_users.insert(0, "the memory image".to_string());
+    }
+    if stdout_users.len() > 1 {
+        eprintln!(
+            "{} cannot share the standard output",
+            stdout_users.join(" and ")
+        );
+        std::process::exit(1);
+    }
+
+    let image = MemoryImage::new(program.ast.title, codegen::generate(&program.ast.lines));
+    write_image(&output_path, &image);
+
+    if let Some(listing_path) = matches.value_of("listing") {
+        write_text(listing_path, &listing::format(&program, &source_program));
+    }
+
+    if let Some(map_path) = matches.value_of("map") {
+        let map_format = matches.value_of("map-format").unwrap().parse().unwrap();
+        let map_order = matches.value_of("map-sort").unwrap().parse().unwrap();
+        let map = map::format(&program, &source_program, map_format, map_order);
+        write_text(map_path, &map);
+    }
+
+    if let Some(debug_info_path) = matches.value_of("debug-info") {
+        let debug_info = debuginfo::generate(&program, &source_program, display_name(input_path));
+        write_text(
+            debug_info_path,
+            &(serde_json::to_string_pretty(&debug_info).unwrap() + "\n"),
+        );
+    }
+}
+
+fn write_text(path: &str, text: &str) {
+    if path == STDIO {
+        print!("{text}");
+    } else if let Err(why) = std::fs::write(path, text) {
+        panic!("{path}: {why}");
+    }
+}
+
+// Assembles several files in parallel and prints a summary of the results
+fn build_all(matches: &ArgMatches, inputs: &[&str]) {
+    let single = ["output", "listing", "map", "debug-info"];
+    if inputs.contains(&STDIO) || single.iter().any(|name| matches.is_present(name)) {
+        eprintln!(
+            "-, --output, --listing, --map and --debug-info cannot be used with multiple inputs"
+        );
+        std::process::exit(1);
+    }
+    let inputs = batch::expand_inputs(inputs).unwrap_or_else(|why| {
+        eprintln!("{why}");
+        std::process::exit(1);
+    });
+    let out_dir = matches.value_of("out-dir").map(Path::new);
+    batch::check_outputs(&inputs, out_dir).unwrap_or_else(|why| {
+        eprintln!("{why}");
+        std::process::exit(1);
+    });
+    if let Some(out_dir) = out_dir {
+        std::fs::create_dir_all(out_dir)
+            .unwrap_or_else(|why| panic!("{}: {why}", out_dir.display()));
+    }
+    let options = batch::Options {
+        out_dir,
+        error_format: matches.value_of("error-format").unwrap().parse().unwrap(),
+        input_encoding: matches.value_of("input-encoding").unwrap().parse().unwrap(),
+        syntax: syntax(matches),
+    };
+    let jobs = match matches.value_of("jobs") {
+        Some(jobs) => jobs.parse().unwrap(),
+        None => std::thread::available_parallelism().map_or(1, |jobs| jobs.get()),
+    };
+
+    let outcomes = batch::assemble_all(&inputs, &options, jobs);
+    for outcome in &outcomes {
+        io::stderr().write_all(&outcome.report).unwrap();
+    }
+    print!("{}", batch::summary(&outcomes));
+    if !outcomes.iter().all(batch::Outcome::succeeded) {
+        std::process::exit(1);
+    }
+}
+
+fn check(matches: &ArgMatches) {
+    let input_path = matches.value_of("input").unwrap();
+    let (source_program, _) = read_source(matches, input_path);
+    analyze(matches, input_path, &source_program);
+}
 
-        // Write a binary file
-        let code = codegen::generate(&ast.lines);
-        write!(file, "MM {}", ast.title).unwrap();
-        for (a, c) in code.iter() {
-            write!(file, "\n{a:04X}  {c:04X}").unwrap();
+fn disasm(matches: &ArgMatches) {
+    let image = read_image(matches.value_of("input").unwrap());
+    print!("{}", disasm::disassemble(&image));
+}
+
+fn read_image(path: &str) -> MemoryImage {
+    read_file(path, InputEncoding::Auto)
+        .0
+        .parse()
+        .unwrap_or_else(|why| {
+            eprintln!("{}: {why}", display_name(path));
+            std::process::exit(1);
+        })
+}
+
+fn diff(matches: &ArgMatches) {
+    let old = read_image(matches.value_of("old").unwrap());
+    let new = read_image(matches.value_of("new").unwrap());
+    let symbols = match matches.value_of("map") {
+        Some(map_path) => {
+            map::read(&read_file(map_path, InputEncoding::Auto).0).unwrap_or_else(|why| {
+                eprintln!("{map_path}: {why}");
+                std::process::exit(1);
+            })
         }
+        None => Vec::new(),
+    };
+
+    let changes = diff::compare(&old, &new);
+    if changes.is_empty() && old.title == new.title {
+        return;
+    }
+    print!("{}", diff::format(&old, &new, &changes, &symbols));
+    std::process::exit(1);
+}
+
+fn run(matches: &ArgMatches) {
+    let input_path = matches.value_of("input").unwrap();
+    let (source_program, _) = read_source(matches, input_path);
+    let program = analyze(matches, input_path, &source_program);
+
+    let mut output_path = std::env::temp_dir();
+    if input_path == STDIO {
+        output_path.push("stdin");
     } else {
-        for err in errs {
-            Report::build(ReportKind::Error, input_path, err.span().start)
-                .with_message("Unexpected token")
-                .with_label(Label::new((input_path, err.span())).with_message(format!(
-                    "Unexpected token {}",
-                    &source_program[err.span()].fg(Color::Red)
-                )))
-                .finish()
-                .print((input_path, Source::from(&source_program)))
-                .unwrap();
+        output_path.push(Path::new(input_path).file_name().unwrap());
+    }
+    output_path.set_extension("b");
+    let image = MemoryImage::new(program.ast.title, codegen::generate(&program.ast.lines));
+    write_image(&output_path, &image);
+
+    let simulator = matches.value_of("simulator").unwrap();
+    match Command::new(simulator).arg(&output_path).status() {
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(why) => {
+            eprintln!("{simulator}: {why}");
+            std::process::exit(1);
         }
     }
 }
+
+fn fmt(matches: &ArgMatches) {
+    let input_path = matches.value_of("input").unwrap();
+    let (source_program, source_encoding) = read_source(matches, input_path);
+    let formatted = format::format(&source_program);
+    if input_path == STDIO && !matches.is_present("check") {
+        print!("{formatted}");
+        return;
+    }
+    if formatted == source_program {
+        return;
+    }
+
+    if matches.is_present("check") {
+        eprintln!("{}: not formatted", display_name(input_path));
+        std::process::exit(1);
+    }
+    // Keep the encoding of the source
+    if let Err(why) = std::fs::write(input_path, encoding::encode(&formatted, source_encoding)) {
+        panic!("{input_path}: {why}");
+    }
+}
+
+fn symbols(matches: &ArgMatches) {
+    let input_path = matches.value_of("input").unwrap();
+    let (source_program, _) = read_source(matches, input_path);
+    let program = analyze(matches, input_path, &source_program);
+
+    let mut symbols: Vec<(&str, MachineAddress)> = symbol::create_symbol_table(&program.ast.lines)
+        .into_iter()
+        .collect();
+    symbols.sort_by_key(|(name, address)| (*address, *name));
+    for (name, address) in symbols {
+        println!("{address:04X}  {name}");
+    }
+}
+
+fn xref(matches: &ArgMatches) {
+    let input_path = matches.value_of("input").unwrap();
+    let (source_program, _) = read_source(matches, input_path);
+    let program = analyze(matches, input_path, &source_program);
+
+    print!(
+        "{}",
+        xref::format(&xref::cross_references(&program, &source_program))
+    );
+}
+
+fn test_corpus(matches: &ArgMatches) {
+    let dir = matches.value_of("dir").unwrap();
+    let error_format: ErrorFormat = matches.value_of("error-format").unwrap().parse().unwrap();
+    let input_encoding = matches.value_of("input-encoding").unwrap().parse().unwrap();
+    let cases = corpus::check_all(
+        Path::new(dir),
+        error_format,
+        input_encoding,
+        syntax(matches),
+    )
+    .unwrap_or_else(|why| {
+        eprintln!("{why}");
+        std::process::exit(1);
+    });
+
+    print!("{}", corpus::report(&cases));
+    if !cases.iter().all(|case| case.passed) {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::assembler::Program;
+use crate::diagnostic::line_column;
+use crate::instruction::MachineAddress;
+use crate::symbol;
+
+use serde_json::json;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MapFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for MapFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(MapFormat::Text),
+            "json" => Ok(MapFormat::Json),
+            _ => Err(format!("{s}: Unknown map format")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MapOrder {
+    Address,
+    Name,
+}
+
+impl FromStr for MapOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "address" => Ok(MapOrder::Address),
+            "name" => Ok(MapOrder::Name),
+            _ => Err(format!("{s}: Unknown map order")),
+        }
+    }
+}
+
+// A symbol in the map
+#[derive(Debug, Eq, PartialEq)]
+pub struct Entry<'a> {
+    pub name: &'a str,
+    pub address: MachineAddress,
+    // 1-origin line of the definition
+    pub line: usize,
+    pub references: usize,
+}
+
+pub fn entries<'a>(program: &'a Program, source: &str, order: MapOrder) -> Vec<Entry<'a>> {
+    let lines = &program.ast.lines;
+    let definitions = symbol::definitions(lines);
+    let mut entries: Vec<Entry> = symbol::create_symbol_table(lines)
+        .into_iter()
+        .map(|(name, address)| Entry {
+            name,
+            address,
+            line: line_column(source, lines[definitions[name]].span.start).0,
+            references: program
+                .references
+                .iter()
+                .filter(|reference| reference.symbol == name)
+                .count(),
+        })
+        .collect();
+
+    match order {
+        MapOrder::Address => entries.sort_by_key(|entry| (entry.address, entry.name)),
+        MapOrder::Name => entries.sort_by_key(|entry| (entry.name, entry.address)),
+    }
+    entries
+}
+
+// Formats the symbol map of a program
+pub fn format(program: &Program, source: &str, format: MapFormat, order: MapOrder) -> String {
+    let entries = entries(program, source, order);
+    match format {
+        MapFormat::Text => {
+            let mut text = format!("; {}\n", program.ast.title);
+            text += "ADDRESS  LINE  REFS  NAME\n";
+            for entry in entries {
+                text += &format!(
+                    "{:04X}{:>9}{:>6}  {}\n",
+                    entry.address, entry.line, entry.references, entry.name
+                );
+            }
+            text
+        }
+        MapFormat::Json => {
+            let symbols: Vec<_> = entries
+                .iter()
+                .map(|entry| {
+                    json!({
+                        "name": entry.name,
+                        "address": format!("{:04X}", entry.address),
+                        "line": entry.line,
+                        "references": entry.references,
+                    })
+                })
+                .collect();
+            let map = json!({ "title": program.ast.title, "symbols": symbols });
+            serde_json::to_string_pretty(&map).unwrap() + "\n"
+        }
+    }
+}
+
+// Reads the names and addresses of symbols from a map in either format
+pub fn read(text: &str) -> Result<Vec<(MachineAddress, String)>, String> {
+    let address = |field: &str| {
+        MachineAddress::from_str_radix(field, 16)
+            .map_err(|_| format!("`{field}` is not an address"))
+    };
+
+    if text.trim_start().starts_with('{') {
+        let map: serde_json::Value = serde_json::from_str(text).map_err(|why| why.to_string())?;
+        let symbols = map["symbols"].as_array().ok_or("`symbols` is not found")?;
+        return symbols
+            .iter()
+            .map(
+                |symbol| match (symbol["address"].as_str(), symbol["name"].as_str()) {
+                    (Some(a), Some(name)) => Ok((address(a)?, name.to_string())),
+                    _ => Err("a symbol needs `address` and `name`".to_string()),
+                },
+            )
+            .collect();
+    }
+
+    text.lines()
+        .filter(|line| !line.starts_with(';') && !line.starts_with("ADDRESS"))
+        .filter(|line| !line.trim().is_empty())
+        .map(
+            |line| match line.split_whitespace().collect::<Vec<&str>>()[..] {
+                [a, _, _, name] => Ok((address(a)?, name.to_string())),
+                _ => Err(format!("`{line}` is not a symbol")),
+            },
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{entries, format, read, Entry, MapFormat, MapOrder};
+    use crate::assembler::analyze;
+    use crate::parser::Syntax;
+
+    const SOURCE: &str = "TITLE T
+        ORG 0x10
+LOOP:   L 1,X
+        BNZ LOOP
+        B LOOP
+X:      DC 0
+PTR:    DC X
+        END
+";
+
+    #[test]
+    fn test_entries() {
+        let program = analyze(SOURCE, Syntax::default()).unwrap();
+        let expected = vec![
+            Entry {
+                name: "LOOP",
+                address: 0x10,
+                line: 3,
+                references: 2,
+            },
+            Entry {
+                name: "X",
+                address: 0x13,
+                line: 6,
+                references: 2,
+            },
+            Entry {
+                name: "PTR",
+                address: 0x14,
+                line: 7,
+                references: 0,
+            },
+        ];
+        assert_eq!(expected, entries(&program, SOURCE, MapOrder::Address));
+
+        let names: Vec<&str> = entries(&program, SOURCE, MapOrder::Name)
+            .iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(vec!["LOOP", "PTR", "X"], names);
+    }
+
+    #[test]
+    fn test_format() {
+        let program = analyze(SOURCE, Syntax::default()).unwrap();
+        let expected = "; T
+ADDRESS  LINE  REFS  NAME
+0010        3     2  LOOP
+0013        6     2  X
+0014        7     0  PTR
+";
+        assert_eq!(
+            expected,
+            format(&program, SOURCE, MapFormat::Text, MapOrder::Address)
+        );
+
+        let json: serde_json::Value =
+            serde_json::from_str(&format(&program, SOURCE, MapFormat::Json, MapOrder::Name))
+                .unwrap();
+        assert_eq!("T", json["title"]);
+        assert_eq!("LOOP", json["symbols"][0]["name"]);
+        assert_eq!("0010", json["symbols"][0]["address"]);
+        assert_eq!(2, json["symbols"][0]["references"]);
+    }
+
+    #[test]
+    fn test_read() {
+        let program = analyze(SOURCE, Syntax::default()).unwrap();
+        let expected = vec![
+            (0x10, "LOOP".to_string()),
+            (0x13, "X".to_string()),
+            (0x14, "PTR".to_string()),
+        ];
+        for map_format in [MapFormat::Text, MapFormat::Json] {
+            let map = format(&program, SOURCE, map_format, MapOrder::Address);
+            assert_eq!(Ok(expected.clone()), read(&map));
+        }
+        assert!(read("0010 3").is_err());
+        assert!(read("{\"symbols\": [{\"name\": \"X\"}]}").is_err());
+    }
+}
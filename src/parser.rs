@@ -1,10 +1,15 @@
+use crate::dialect::{Dialect, Feature};
+use crate::incbin::ByteOrder;
 use crate::instruction::*;
-use crate::token::Token;
+use crate::lexer;
+use crate::token::{self, Token};
 
 use chumsky::prelude::*;
 use chumsky::Stream;
 use logos::Span;
 
+use std::collections::HashMap;
+
 #[derive(Debug, Eq, Hash, PartialEq)]
 pub struct Ast<'a> {
     pub title: &'a str,
@@ -15,17 +20,331 @@ pub struct Ast<'a> {
 pub struct ProgramLine<'a> {
     pub label: Option<&'a str>,
     pub instruction: Option<Instruction<'a>>,
+    // Span from the label or the mnemonic to the end of the operands
+    pub span: Span,
+    // Span of the symbol which the instruction refers to, if any
+    pub symbol_span: Option<Span>,
+}
+
+impl ProgramLine<'_> {
+    // Span of the instruction, without the label
+    pub fn instruction_span(&self, source: &str) -> Span {
+        // Words after the first of a DC line share its span but not its label
+        let text = &source[self.span.clone()];
+        match &lexer::tokenize(text)[..] {
+            [(Token::String(_), _), (Token::Colon, _), rest @ ..] => {
+                let start = rest.first().map_or(text.len(), |(_, span)| span.start);
+                self.span.start + start..self.span.end
+            }
+            _ => self.span.clone(),
+        }
+    }
+}
+
+// Options of the source syntax
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Syntax {
+    pub dialect: Dialect,
+    // Reads bare ORG operands such as `100` as decimal instead of hexadecimal as the textbook does
+    pub decimal_org: bool,
+    // Requires mnemonics, directives and device names in upper case
+    pub case_sensitive: bool,
+    // Rewrites branches whose targets are out of range into long forms
+    pub relax_branches: bool,
+    // Order of the two bytes of each word read by INCBIN
+    pub byte_order: ByteOrder,
+}
+
+impl Syntax {
+    // The classic dialect always reads ORG as the textbook does, and the strict one never does
+    pub fn textbook_org(&self) -> bool {
+        match self.dialect {
+            Dialect::Classic => true,
+            Dialect::Extended => !self.decimal_org,
+            Dialect::Strict => false,
+        }
+    }
+
+    // Whether `word` is the mnemonic, directive or device name `keyword`.
+    // The classic dialect accepts only upper case as the textbook does.
+    pub fn is_keyword(&self, word: &str, keyword: &str) -> bool {
+        if self.case_sensitive || self.dialect == Dialect::Classic {
+            word == keyword
+        } else {
+            word.eq_ignore_ascii_case(keyword)
+        }
+    }
+}
+
+// A word of a line, with the span of the symbol which it refers to
+type Word<'a> = (Instruction<'a>, Option<Span>);
+
+// An operand of DC, which is a word or a string literal
+#[derive(Clone, Debug)]
+enum Datum<'a> {
+    Word(Word<'a>),
+    Text(Vec<u8>),
+}
+
+// Packs one or two characters into a word, the first one in the upper byte if there are two
+fn pack(chars: &[u8]) -> MachineCode {
+    chars
+        .iter()
+        .fold(0, |word, c| (word << 8) | *c as MachineCode)
+}
+
+// Lays out the operands of DC in words. Strings take one character per word, or two characters
+// per word with `packed` as `'AB` does, and `terminated` appends a zero word.
+fn layout_data(data: Vec<Datum>, packed: bool, terminated: bool) -> Vec<Word> {
+    let word = |value| {
+        let instruction = Instruction::Dc {
+            value,
+            unresolved_symbol: None,
+        };
+        (instruction, None)
+    };
+    let mut words = Vec::new();
+    for datum in data {
+        match datum {
+            Datum::Word(word) => words.push(word),
+            Datum::Text(bytes) if packed => words.extend(
+                bytes
+                    .chunks(2)
+                    .map(|pair| word(pack(pair) << (8 * (2 - pair.len())))),
+            ),
+            Datum::Text(bytes) => words.extend(bytes.into_iter().map(MachineCode::from).map(word)),
+        }
+    }
+    if terminated {
+        words.push(word(0));
+    }
+    words
+}
+
+// Mnemonics of each group of instructions, from which the parser and `mnemonics` are built
+const OPECODES_1: &[(&str, Opecode1)] = &[
+    ("ADD", Opecode1::Add),
+    ("SUB", Opecode1::Sub),
+    ("AND", Opecode1::And),
+    ("OR", Opecode1::Or),
+    ("XOR", Opecode1::Xor),
+    ("MULT", Opecode1::Mult),
+    ("DIV", Opecode1::Div),
+    ("CMP", Opecode1::Cmp),
+    ("EX", Opecode1::Ex),
+];
+
+const OPECODES_2: &[(&str, Opecode2)] = &[
+    ("LC", Opecode2::Lc),
+    ("PUSH", Opecode2::Push),
+    ("POP", Opecode2::Pop),
+];
+
+const OPECODES_3: &[(&str, Opecode3)] = &[
+    ("SL", Opecode3::Sl),
+    ("SA", Opecode3::Sa),
+    ("SC", Opecode3::Sc),
+    ("BIX", Opecode3::Bix),
+];
+
+const OPECODES_4: &[(&str, Opecode4)] = &[
+    ("LEA", Opecode4::Lea),
+    ("LX", Opecode4::Lx),
+    ("STX", Opecode4::Stx),
+];
+
+const OPECODES_5: &[(&str, Opecode5)] = &[
+    ("L", Opecode5::L),
+    ("ST", Opecode5::St),
+    ("LA", Opecode5::La),
+];
+
+const OPECODES_6: &[(&str, Opecode6)] = &[
+    ("BDIS", Opecode6::Bdis),
+    ("BP", Opecode6::Bp),
+    ("BZ", Opecode6::Bz),
+    ("BM", Opecode6::Bm),
+    ("BC", Opecode6::Bc),
+    ("BNP", Opecode6::Bnp),
+    ("BNZ", Opecode6::Bnz),
+    ("BNM", Opecode6::Bnm),
+    ("BNC", Opecode6::Bnc),
+    ("B", Opecode6::B),
+    ("BI", Opecode6::Bi),
+    ("BSR", Opecode6::Bsr),
+];
+
+const OPECODES_7: &[(&str, Opecode7)] = &[("RIO", Opecode7::Rio), ("WIO", Opecode7::Wio)];
+
+const OPECODES_8: &[(&str, Opecode8)] = &[
+    ("RET", Opecode8::Ret),
+    ("NOP", Opecode8::Nop),
+    ("HLT", Opecode8::Hlt),
+];
+
+// DC, with P for packed strings and Z for a zero terminator
+const DATA_DIRECTIVES: &[(&str, (bool, bool))] = &[
+    ("DC", (false, false)),
+    ("DCZ", (false, true)),
+    ("DCP", (true, false)),
+    ("DCPZ", (true, true)),
+];
+
+// CLR loads 0, and INC and DEC add and subtract 1
+const REGISTER_OPERATIONS: &[(&str, Option<Opecode1>)] = &[
+    ("CLR", None),
+    ("INC", Some(Opecode1::Add)),
+    ("DEC", Some(Opecode1::Sub)),
+];
+
+// Directives matched by `keyword` in the instruction field, or by the expansion of repeat blocks
+const DIRECTIVES: &[&str] = &[
+    "DS", "FILL", "ALIGN", "RESERVE", "ORG", "LTORG", "REPT", "IRP", "ENDR", "INCBIN", "REG", "END",
+];
+
+// Mnemonics which expand into several instructions
+pub const PSEUDO_INSTRUCTIONS: &[&str] = &["CLR", "INC", "DEC", "LI", "JMP"];
+
+// Mnemonics and directives accepted in the instruction field
+pub fn mnemonics() -> Vec<&'static str> {
+    fn names<T>(table: &'static [(&'static str, T)]) -> impl Iterator<Item = &'static str> {
+        table.iter().map(|(name, _)| *name)
+    }
+    let mut mnemonics: Vec<&str> = names(OPECODES_1)
+        .chain(names(OPECODES_2))
+        .chain(names(OPECODES_3))
+        .chain(names(OPECODES_4))
+        .chain(names(OPECODES_5))
+        .chain(names(OPECODES_6))
+        .chain(names(OPECODES_7))
+        .chain(names(OPECODES_8))
+        .chain(names(DATA_DIRECTIVES))
+        .chain(DIRECTIVES.iter().copied())
+        .collect();
+    mnemonics.extend(PSEUDO_INSTRUCTIONS);
+    mnemonics
 }
 
-fn parser<'a>() -> impl Parser<Token<'a>, Ast<'a>, Error = Simple<Token<'a>>> {
+// Loads a 16-bit constant with as few words as possible, as LI does
+fn load_immediate<'a>(rb: Register, value: u16) -> Vec<Instruction<'a>> {
+    let (high, low) = ((value >> 8) as u8, value as u8);
+    if high == 0 {
+        return vec![Instruction::Group2 {
+            op: Opecode2::Lc,
+            rb,
+            constant: low,
+        }];
+    }
+    let mut words = vec![
+        Instruction::Group2 {
+            op: Opecode2::Lc,
+            rb: rb.clone(),
+            constant: high,
+        },
+        Instruction::Group3 {
+            op: Opecode3::Sl,
+            rb: rb.clone(),
+            constant: 8,
+        },
+    ];
+    if low != 0 {
+        words.push(Instruction::Group1 {
+            op: Opecode1::Or,
+            ra: Register::Zero,
+            rb,
+            constant: low,
+        });
+    }
+    words
+}
+
+// Register numbers of aliases defined by lines such as `SP REG 3`, or None if malformed
+type RegisterAliases<'a> = HashMap<&'a str, Option<u16>>;
+
+// Collects register aliases before parsing, so that they can be used before their definitions
+fn register_aliases<'a>(tokens: &[(Token<'a>, Span)], syntax: Syntax) -> RegisterAliases<'a> {
+    tokens
+        .split(|(token, _)| *token == Token::Eol)
+        .filter_map(|line| match line {
+            [(Token::String(name), _), (Token::String(reg), _), rest @ ..]
+                if syntax.is_keyword(reg, "REG") =>
+            {
+                let number = match rest {
+                    [(Token::Decimal(number), _)] => Some(*number),
+                    _ => None,
+                };
+                Some((*name, number))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn invalid_register_alias(name: &str, number: u16) -> String {
+    format!("register alias `{name}` names register {number}, but registers are 0-3")
+}
+
+// Matches a keyword such as `TITLE`, in any case if the syntax allows
+#[allow(clippy::result_large_err)]
+fn keyword<'a>(
+    name: &'static str,
+    syntax: Syntax,
+) -> impl Parser<Token<'a>, (), Error = Simple<Token<'a>>> + Clone {
+    debug_assert!(name == "TITLE" || mnemonics().contains(&name));
+    filter_map(move |span, token| match token {
+        Token::String(s) if syntax.is_keyword(s, name) => Ok(()),
+        _ => Err(Simple::expected_input_found(
+            span,
+            [Some(Token::String(name))],
+            Some(token),
+        )),
+    })
+}
+
+// Matches a mnemonic of `table` and gives its value
+#[allow(clippy::result_large_err)]
+fn mnemonic<'a, T: Clone + 'static>(
+    table: &'static [(&'static str, T)],
+    syntax: Syntax,
+) -> impl Parser<Token<'a>, T, Error = Simple<Token<'a>>> + Clone {
+    filter_map(move |span, token| match token {
+        Token::String(s) => table
+            .iter()
+            .find(|(name, _)| syntax.is_keyword(s, name))
+            .map(|(_, value)| value.clone())
+            .ok_or_else(|| Simple::expected_input_found(span, [], Some(token))),
+        _ => Err(Simple::expected_input_found(span, [], Some(token))),
+    })
+}
+
+#[allow(clippy::result_large_err)]
+fn parser<'a, 'b>(
+    syntax: Syntax,
+    aliases: &'b RegisterAliases<'a>,
+) -> impl Parser<Token<'a>, Ast<'a>, Error = Simple<Token<'a>>> + 'b {
     let string = select! { Token::String(s) => s };
     let decimal = select! { Token::Decimal(d) => d };
+    // A character literal is one or two 8-bit characters
+    let character = select! {
+        Token::Chars(s) => s
+            .chars()
+            .map(|c| u8::try_from(c).map_err(|_| format!("character `{c}` does not fit in 8 bits")))
+            .collect(),
+        Token::Char(s) => token::unescape(s),
+    }
+    .try_map(|chars: Result<Vec<u8>, String>, span| {
+        chars
+            .map(|chars| pack(&chars))
+            .map_err(|why| Simple::custom(span, why))
+    });
     let unsigned_integer = select! {
         Token::Decimal(d) => d,
         Token::Hexadecimal(h) => h,
         Token::Octal(o) => o,
         Token::Binary(b) => b,
-    };
+    }
+    .or(character)
+    .labelled("constant");
     let signed_integer = just(Token::Plus)
         .or(just(Token::Minus))
         .or_not()
@@ -37,12 +356,45 @@ fn parser<'a>() -> impl Parser<Token<'a>, Ast<'a>, Error = Simple<Token<'a>>> {
                 d
             }
         });
+    // `R0` to `R3`, or an alias defined by REG
+    let register_name = move |name: &str| {
+        ["R0", "R1", "R2", "R3"]
+            .iter()
+            .position(|keyword| syntax.is_keyword(name, keyword))
+            .map(|number| number as u16)
+            .or_else(|| aliases.get(name).copied().flatten())
+    };
     let register = select! {
         Token::Decimal(0) => Register::Zero,
         Token::Decimal(1) => Register::One,
         Token::Decimal(2) => Register::Two,
         Token::Decimal(3) => Register::Three,
-    };
+    }
+    .or(filter_map(move |span, token| match token {
+        Token::String(name) => register_name(name)
+            .map(|number| (name, number))
+            .ok_or_else(|| Simple::expected_input_found(span, [], Some(token))),
+        _ => Err(Simple::expected_input_found(span, [], Some(token))),
+    })
+    // Errors are emitted rather than returned, so that they are not lost among alternatives
+    .validate(move |(name, number), span: Span, emit| {
+        if !syntax.dialect.allows(Feature::RegisterName) {
+            emit(Simple::custom(
+                span.clone(),
+                syntax.dialect.disallowed(Feature::RegisterName),
+            ));
+        }
+        Register::try_from(number).unwrap_or_else(|_| {
+            emit(Simple::custom(span, invalid_register_alias(name, number)));
+            Register::Zero
+        })
+    }))
+    .labelled("register 0-3");
+    let index_register = register
+        .delimited_by(just(Token::Lparen), just(Token::Rparen))
+        .labelled("index register (0-3)");
+    let comma_after_register = just(Token::Comma).labelled("`,` after register");
+    // An address, with the span of its symbol
     let address = just(Token::Star)
         .ignore_then(
             just(Token::Plus)
@@ -51,7 +403,7 @@ fn parser<'a>() -> impl Parser<Token<'a>, Ast<'a>, Error = Simple<Token<'a>>> {
                 .or_not(),
         )
         .map(|op_and_d| {
-            Address::Constant(if let Some((op, d)) = op_and_d {
+            let address = Address::Constant(if let Some((op, d)) = op_and_d {
                 if op == Token::Plus {
                     d as MachineAddress
                 } else {
@@ -59,46 +411,44 @@ fn parser<'a>() -> impl Parser<Token<'a>, Ast<'a>, Error = Simple<Token<'a>>> {
                 }
             } else {
                 0
-            })
+            });
+            (address, None)
         })
         .or(string
+            .map_with_span(|symbol_name, span| (symbol_name, span))
             .then(
                 just(Token::Plus)
                     .or(just(Token::Minus))
                     .then(decimal)
                     .or_not(),
             )
-            .map(|(symbol_name, op_and_d)| Address::Unresolved {
-                symbol_name,
-                offset: {
-                    if let Some((op, d)) = op_and_d {
-                        if op == Token::Plus {
-                            d as i16
+            .map(|((symbol_name, span), op_and_d)| {
+                let address = Address::Unresolved {
+                    symbol_name,
+                    offset: {
+                        if let Some((op, d)) = op_and_d {
+                            if op == Token::Plus {
+                                d as i16
+                            } else {
+                                -(d as i16)
+                            }
                         } else {
-                            -(d as i16)
+                            0i16
                         }
-                    } else {
-                        0i16
-                    }
-                },
-            }));
+                    },
+                };
+                (address, Some(span))
+            }))
+        .labelled("address");
 
-    let opecode_1th = select! {
-        Token::String("ADD") => Opecode1::Add,
-        Token::String("SUB") => Opecode1::Sub,
-        Token::String("AND") => Opecode1::And,
-        Token::String("OR") => Opecode1::Or,
-        Token::String("XOR") => Opecode1::Xor,
-        Token::String("MULT") => Opecode1::Mult,
-        Token::String("DIV") => Opecode1::Div,
-        Token::String("CMP") => Opecode1::Cmp,
-        Token::String("EX") => Opecode1::Ex,
-    };
-    let instruction_1th_first_half = opecode_1th.then(register).then_ignore(just(Token::Comma));
+    let opecode_1th = mnemonic(OPECODES_1, syntax);
+    let instruction_1th_first_half = opecode_1th
+        .then(register)
+        .then_ignore(comma_after_register.clone());
     let instruction_1th = instruction_1th_first_half
         .clone()
         .then(unsigned_integer)
-        .then(register.delimited_by(just(Token::Lparen), just(Token::Rparen)))
+        .then(index_register.clone())
         .map(|(((op, rb), constant), ra)| Instruction::Group1 {
             op,
             ra,
@@ -107,7 +457,7 @@ fn parser<'a>() -> impl Parser<Token<'a>, Ast<'a>, Error = Simple<Token<'a>>> {
         })
         .or(instruction_1th_first_half
             .clone()
-            .then(register.delimited_by(just(Token::Lparen), just(Token::Rparen)))
+            .then(index_register.clone())
             .map(|((op, rb), ra)| Instruction::Group1 {
                 op,
                 ra,
@@ -123,14 +473,10 @@ fn parser<'a>() -> impl Parser<Token<'a>, Ast<'a>, Error = Simple<Token<'a>>> {
                 constant: constant as u8,
             }));
 
-    let opecode_2th = select! {
-        Token::String("LC") => Opecode2::Lc,
-        Token::String("PUSH") => Opecode2::Push,
-        Token::String("POP") => Opecode2::Pop,
-    };
+    let opecode_2th = mnemonic(OPECODES_2, syntax);
     let instruction_2th = opecode_2th
         .then(register)
-        .then_ignore(just(Token::Comma))
+        .then_ignore(comma_after_register.clone())
         .then(unsigned_integer)
         .map(|((op, rb), constant)| Instruction::Group2 {
             op,
@@ -138,15 +484,10 @@ fn parser<'a>() -> impl Parser<Token<'a>, Ast<'a>, Error = Simple<Token<'a>>> {
             constant: constant as u8,
         });
 
-    let opecode_3th = select! {
-        Token::String("SL") => Opecode3::Sl,
-        Token::String("SA") => Opecode3::Sa,
-        Token::String("SC") => Opecode3::Sc,
-        Token::String("BIX") => Opecode3::Bix,
-    };
+    let opecode_3th = mnemonic(OPECODES_3, syntax);
     let instruction_3th = opecode_3th
         .then(register)
-        .then_ignore(just(Token::Comma))
+        .then_ignore(comma_after_register.clone())
         .then(signed_integer.clone())
         .map(|((op, rb), constant)| Instruction::Group3 {
             op,
@@ -154,16 +495,12 @@ fn parser<'a>() -> impl Parser<Token<'a>, Ast<'a>, Error = Simple<Token<'a>>> {
             constant: constant as i8,
         });
 
-    let opecode_4th = select! {
-        Token::String("LEA") => Opecode4::Lea,
-        Token::String("LX") => Opecode4::Lx,
-        Token::String("STX") => Opecode4::Stx,
-    };
+    let opecode_4th = mnemonic(OPECODES_4, syntax);
     let instruction_4th = opecode_4th
         .then(register)
-        .then_ignore(just(Token::Comma))
+        .then_ignore(comma_after_register.clone())
         .then(signed_integer.clone().or_not())
-        .then(register.delimited_by(just(Token::Lparen), just(Token::Rparen)))
+        .then(index_register)
         .map(|(((op, rb), constant), ra)| Instruction::Group4 {
             op,
             ra,
@@ -175,142 +512,374 @@ fn parser<'a>() -> impl Parser<Token<'a>, Ast<'a>, Error = Simple<Token<'a>>> {
             },
         });
 
-    let opecode_5th = select! {
-        Token::String("L") => Opecode5::L,
-        Token::String("ST") => Opecode5::St,
-        Token::String("LA") => Opecode5::La,
-    };
+    let opecode_5th = mnemonic(OPECODES_5, syntax);
+    // `=X"1234"` is the address of a word in the literal pool
+    let literal = just(Token::Equal)
+        .ignore_then(signed_integer.clone())
+        .map(|value| (Address::Literal(value), None));
     let instruction_5th = opecode_5th
         .then(register)
-        .then_ignore(just(Token::Comma))
-        .then(address.clone())
-        .map(|((op, rb), address)| Instruction::Group5 { op, rb, address });
-
-    let opecode_6th = select! {
-        Token::String("BDIS") => Opecode6::Bdis,
-        Token::String("BP") => Opecode6::Bp,
-        Token::String("BZ") => Opecode6::Bz,
-        Token::String("BM") => Opecode6::Bm,
-        Token::String("BC") => Opecode6::Bc,
-        Token::String("BNP") => Opecode6::Bnp,
-        Token::String("BNZ") => Opecode6::Bnz,
-        Token::String("BNM") => Opecode6::Bnm,
-        Token::String("BNC") => Opecode6::Bnc,
-        Token::String("B") => Opecode6::B,
-        Token::String("BI") => Opecode6::Bi,
-        Token::String("BSR") => Opecode6::Bsr,
-    };
+        .then_ignore(just(Token::Comma).labelled("`,` between register and address"))
+        .then(address.clone().or(literal))
+        .map(|((op, rb), (address, symbol_span))| {
+            (Instruction::Group5 { op, rb, address }, symbol_span)
+        });
+
+    let opecode_6th = mnemonic(OPECODES_6, syntax);
     let instruction_6th = opecode_6th
-        .then(address)
-        .map(|(op, address)| Instruction::Group6 { op, address });
+        .then(address.clone())
+        .map(|(op, (address, symbol_span))| (Instruction::Group6 { op, address }, symbol_span));
 
-    let opecode_7th = select! {
-        Token::String("RIO") => Opecode7::Rio,
-        Token::String("WIO") => Opecode7::Wio,
-    };
+    let opecode_7th = mnemonic(OPECODES_7, syntax);
     let device = select! {
-        Token::String("CR") => Device::Cr,
+        Token::String(s) if syntax.is_keyword(s, "CR") => Device::Cr,
         Token::Decimal(0) => Device::Cr,
-        Token::String("LPT") => Device::Lpt,
+        Token::String(s) if syntax.is_keyword(s, "LPT") => Device::Lpt,
         Token::Decimal(1) => Device::Lpt,
-    };
+    }
+    .labelled("device CR or LPT");
     let instruction_7th = opecode_7th
         .then(device)
         .map(|(op, device)| Instruction::Group7 { op, device });
 
-    let opecode_8th = select! {
-        Token::String("RET") => Opecode8::Ret,
-        Token::String("NOP") => Opecode8::Nop,
-        Token::String("HLT") => Opecode8::Hlt,
-    };
+    let opecode_8th = mnemonic(OPECODES_8, syntax);
     let instruction_8th = opecode_8th.map(|op| Instruction::Group8 { op });
 
-    let instruction_9th = just(Token::String("DC"))
+    let datum = signed_integer
+        .clone()
+        .map(|value| {
+            let instruction = Instruction::Dc {
+                value,
+                unresolved_symbol: None,
+            };
+            (instruction, None)
+        })
+        .or(string.map_with_span(|unresolved_symbol, span| {
+            let instruction = Instruction::Dc {
+                value: 0,
+                unresolved_symbol: Some(unresolved_symbol),
+            };
+            (instruction, Some(span))
+        }))
+        .map(Datum::Word)
+        .or(select! { Token::Text(s) => s }
+            .try_map(|s, span| token::unescape(s).map_err(|why| Simple::custom(span, why)))
+            .map(Datum::Text));
+    let data = mnemonic(DATA_DIRECTIVES, syntax)
+        .then(datum.separated_by(just(Token::Comma)).at_least(1))
+        .try_map(move |((packed, terminated), data), span| {
+            let dialect = syntax.dialect;
+            if (packed || terminated) && !dialect.allows(Feature::DataDirective) {
+                return Err(Simple::custom(
+                    span,
+                    dialect.disallowed(Feature::DataDirective),
+                ));
+            }
+            if data.len() > 1 && !dialect.allows(Feature::DataList) {
+                return Err(Simple::custom(span, dialect.disallowed(Feature::DataList)));
+            }
+            let words = layout_data(data, packed, terminated);
+            if words.is_empty() {
+                Err(Simple::custom(span, "DC defines no words"))
+            } else {
+                Ok(words)
+            }
+        });
+
+    // Pseudo-instructions, which expand into sequences of real instructions
+    let register_operation =
+        mnemonic(REGISTER_OPERATIONS, syntax)
+            .then(register)
+            .map(|(op, rb)| {
+                let instruction = match op {
+                    None => Instruction::Group2 {
+                        op: Opecode2::Lc,
+                        rb,
+                        constant: 0,
+                    },
+                    Some(op) => Instruction::Group1 {
+                        op,
+                        ra: Register::Zero,
+                        rb,
+                        constant: 1,
+                    },
+                };
+                vec![(instruction, None)]
+            });
+    let load_immediate = keyword("LI", syntax)
+        .ignore_then(register)
+        .then_ignore(comma_after_register)
+        .then(signed_integer.clone())
+        .map(|(rb, value)| {
+            load_immediate(rb, value)
+                .into_iter()
+                .map(|instruction| (instruction, None))
+                .collect()
+        });
+    // JMP branches to any address through the word after BI. The word holds the address itself,
+    // so `*` relative to the instruction cannot be used.
+    let far_jump = keyword("JMP", syntax)
         .ignore_then(
-            signed_integer
-                .map(|value| Instruction::Dc {
-                    value,
-                    unresolved_symbol: None,
-                })
-            .or(
-                (select! { Token::Chars(s) => ((s.chars().next().unwrap() as MachineCode) << 8) + (s.chars().nth(1).unwrap() as MachineCode) })
-                .map(|value| Instruction::Dc {
-                    value,
-                    unresolved_symbol: None,
-                })
-            )
-            .or(string
-                .map(|unresolved_symbol| Instruction::Dc {
-                    value: 0,
-                    unresolved_symbol: Some(unresolved_symbol),
+            address
+                .clone()
+                .try_map(|(address, symbol_span), span| match address {
+                    Address::Unresolved {
+                        symbol_name,
+                        offset,
+                    } => {
+                        let instruction = Instruction::Dc {
+                            value: offset as u16,
+                            unresolved_symbol: Some(symbol_name),
+                        };
+                        Ok((instruction, symbol_span))
+                    }
+                    _ => Err(Simple::custom(
+                        span,
+                        "JMP needs a symbol or an absolute address, not `*`",
+                    )),
                 })
-            )
-        ).or(
-            just(Token::String("DS"))
-                .ignore_then(decimal)
-                .map(Instruction::Ds)
-        ).or(
-            just(Token::String("ORG"))
-                .ignore_then(select! {
-                    Token::Decimal(d) => MachineAddress::from_str_radix(&d.to_string(), 16).unwrap(),
-                    Token::NoPrefixHexadecimal(h) => h,
-                }.or(
-                    string.try_map(|s, span| MachineAddress::from_str_radix(s, 16)
-                    .map_err(|e| Simple::custom(span, format!("{e}"))))
+                .or(unsigned_integer.map(|value| {
+                    let instruction = Instruction::Dc {
+                        value,
+                        unresolved_symbol: None,
+                    };
+                    (instruction, None)
+                }))
+                .labelled("address"),
+        )
+        .map(|target| {
+            let branch = Instruction::Group6 {
+                op: Opecode6::Bi,
+                address: Address::Constant(1),
+            };
+            vec![(branch, None), target]
+        });
+    let pseudo_instruction =
+        register_operation
+            .or(load_immediate)
+            .or(far_jump)
+            .try_map(move |words, span| {
+                if syntax.dialect.allows(Feature::PseudoInstruction) {
+                    Ok(words)
+                } else {
+                    Err(Simple::custom(
+                        span,
+                        syntax.dialect.disallowed(Feature::PseudoInstruction),
+                    ))
+                }
+            });
+
+    // The textbook reads bare ORG operands as hexadecimal, and prefixed ones are the same in both
+    let textbook_address = move |digits: &str, span: Span| {
+        if !syntax.textbook_org() {
+            return Err(Simple::custom(
+                span,
+                format!(
+                    "`{digits}` is hexadecimal only in the textbook syntax; write `0x{digits}`"
+                ),
+            ));
+        }
+        MachineAddress::from_str_radix(digits, 16)
+            .map_err(|_| Simple::custom(span, format!("`{digits}` is not a hexadecimal address")))
+    };
+    let org_address = select! {
+        Token::Hexadecimal(h) => h,
+        Token::Octal(o) => o,
+        Token::Binary(b) => b,
+    }
+    .or(decimal.try_map(move |d, span| match syntax.textbook_org() {
+        true => textbook_address(&d.to_string(), span),
+        false => Ok(d),
+    }))
+    .or(select! { Token::NoPrefixHexadecimal(h) => h }
+        .try_map(move |h, span| textbook_address(&format!("{h:X}"), span)))
+    .or(string.try_map(textbook_address))
+    .labelled("address");
+
+    // `FILL 4,-1` is `DS 4,-1`. RESERVE allocates addresses without words in the image.
+    let layout = keyword("FILL", syntax)
+        .ignore_then(unsigned_integer.labelled("size"))
+        .then_ignore(just(Token::Comma).labelled("`,` after size"))
+        .then(signed_integer.clone().labelled("fill value"))
+        .map(|(size, value)| Instruction::Ds(size, value))
+        .or(keyword("ALIGN", syntax)
+            .ignore_then(unsigned_integer.labelled("alignment"))
+            .try_map(|alignment, span| {
+                if alignment == 0 {
+                    Err(Simple::custom(span, "alignment must be 1 or more"))
+                } else {
+                    Ok(Instruction::Align(alignment))
+                }
+            }))
+        .or(keyword("RESERVE", syntax)
+            .ignore_then(unsigned_integer.labelled("size"))
+            .map(Instruction::Reserve))
+        .try_map(move |instruction, span| {
+            if syntax.dialect.allows(Feature::LayoutDirective) {
+                Ok(instruction)
+            } else {
+                Err(Simple::custom(
+                    span,
+                    syntax.dialect.disallowed(Feature::LayoutDirective),
                 ))
-                .map(Instruction::Org)
-        );
+            }
+        });
 
-    let instruction = string
-        .then_ignore(just(Token::Colon))
-        .or_not()
+    // `INCBIN "table.bin",2,16` includes 16 bytes from the third
+    let binary_file = keyword("INCBIN", syntax)
+        .ignore_then(select! { Token::Text(path) => path }.labelled("file name"))
+        .then(
+            just(Token::Comma)
+                .ignore_then(unsigned_integer.labelled("offset"))
+                .then(
+                    just(Token::Comma)
+                        .ignore_then(unsigned_integer.labelled("length"))
+                        .or_not(),
+                )
+                .or_not(),
+        )
+        .try_map(move |(path, range), span| {
+            if !syntax.dialect.allows(Feature::BinaryInclusion) {
+                return Err(Simple::custom(
+                    span,
+                    syntax.dialect.disallowed(Feature::BinaryInclusion),
+                ));
+            }
+            let (offset, length) = range.unwrap_or((0, None));
+            Ok(Instruction::Incbin {
+                path,
+                offset,
+                length,
+            })
+        });
+
+    // `DS 4,-1` fills the area with -1
+    let instruction_9th = keyword("DS", syntax)
+        .ignore_then(unsigned_integer.labelled("size"))
         .then(
+            just(Token::Comma)
+                .ignore_then(signed_integer.clone().labelled("fill value"))
+                .or_not(),
+        )
+        .try_map(move |(size, value), span| match value {
+            Some(_) if !syntax.dialect.allows(Feature::FillValue) => Err(Simple::custom(
+                span,
+                syntax.dialect.disallowed(Feature::FillValue),
+            )),
+            _ => Ok(Instruction::Ds(size, value.unwrap_or(0))),
+        })
+        .or(layout)
+        .or(binary_file)
+        .or(keyword("ORG", syntax)
+            .ignore_then(org_address)
+            .map(Instruction::Org))
+        .or(keyword("LTORG", syntax).try_map(move |_, span| {
+            if syntax.dialect.allows(Feature::LiteralPool) {
+                Ok(Instruction::Ltorg)
+            } else {
+                Err(Simple::custom(
+                    span,
+                    syntax.dialect.disallowed(Feature::LiteralPool),
+                ))
+            }
+        }));
+
+    // `SP REG 3` names register 3 `SP`. Aliases are collected before parsing, so the line only
+    // checks its number.
+    let register_alias = select! { Token::String(name) if aliases.contains_key(name) => name }
+        .then_ignore(keyword("REG", syntax))
+        .then(decimal.labelled("register number"))
+        .try_map(move |(name, number), span| {
+            if !syntax.dialect.allows(Feature::RegisterName) {
+                return Err(Simple::custom(
+                    span,
+                    syntax.dialect.disallowed(Feature::RegisterName),
+                ));
+            }
+            match Register::try_from(number) {
+                Ok(_) => Ok((None, None)),
+                Err(_) => Err(Simple::custom(span, invalid_register_alias(name, number))),
+            }
+        });
+
+    let instruction = register_alias
+        .or(string.then_ignore(just(Token::Colon)).or_not().then(
             instruction_1th
                 .or(instruction_2th)
                 .or(instruction_3th)
                 .or(instruction_4th)
+                .map(|instruction| (instruction, None))
                 .or(instruction_5th)
                 .or(instruction_6th)
-                .or(instruction_7th)
-                .or(instruction_8th)
-                .or(instruction_9th)
+                .or(instruction_7th
+                    .or(instruction_8th)
+                    .or(instruction_9th)
+                    .map(|instruction| (instruction, None)))
+                .map(|word| vec![word])
+                .or(data)
+                .or(pseudo_instruction)
+                .labelled("instruction")
                 .or_not(),
-        )
-        .then_ignore(just(Token::Eol))
+        ))
+        .map_with_span(|(label, instructions), span| (label, instructions, span))
+        .then_ignore(just(Token::Eol).labelled("end of line"))
         .recover_with(skip_then_retry_until([Token::Eol]));
 
-    let program_head = just(Token::String("TITLE")).ignore_then(string);
+    let program_head = keyword("TITLE", syntax).ignore_then(string.labelled("title"));
     let program_body = instruction.repeated().at_least(1).map(|lines| {
         lines
             .into_iter()
-            .filter(|(label, instruction)| label.is_some() || instruction.is_some())
-            .map(|(label, instruction)| ProgramLine { label, instruction })
+            .filter(|(label, instructions, _)| label.is_some() || instructions.is_some())
+            .flat_map(
+                |(label, instructions, span): (_, _, Span)| match instructions {
+                    // A line with several words is split into a line for each word
+                    Some(instructions) => instructions
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, (instruction, symbol_span))| ProgramLine {
+                            label: label.filter(|_| i == 0),
+                            instruction: Some(instruction),
+                            span: span.clone(),
+                            symbol_span,
+                        })
+                        .collect(),
+                    None => vec![ProgramLine {
+                        label,
+                        instruction: None,
+                        span,
+                        symbol_span: None,
+                    }],
+                },
+            )
             .collect()
     });
-    let program_tail = just(Token::String("END"));
+    let program_tail = keyword("END", syntax).labelled("END");
 
     let program = program_head
         .then(program_body.delimited_by(just(Token::Eol), program_tail))
-        .then_ignore(end());
+        .then_ignore(end().labelled("end of program"));
 
     program.map(|(title, lines)| Ast { title, lines })
 }
 
-pub fn parse(tokens: Vec<(Token, Span)>) -> (Option<Ast>, Vec<Simple<Token>>) {
-    let start = if let Some(start) = tokens.iter().position(|(x, _)| *x != Token::Eol) {
-        start
-    } else {
-        0
-    };
+pub fn parse(tokens: Vec<(Token, Span)>, syntax: Syntax) -> (Option<Ast>, Vec<Simple<Token>>) {
+    let start = tokens
+        .iter()
+        .position(|(x, _)| *x != Token::Eol)
+        .unwrap_or(0);
     let end = if let Some(end) = tokens.iter().rposition(|(x, _)| *x != Token::Eol) {
         end + 1
     } else {
         tokens.len()
     };
-    parser().parse_recovery(Stream::from_iter(
-        start..end,
-        tokens[start..end].iter().cloned(),
-    ))
+    let eoi = tokens[..end]
+        .last()
+        .map_or(0..0, |(_, span)| span.end..span.end);
+    let aliases = register_aliases(&tokens, syntax);
+    // Bound to a variable so that the parser borrowing `aliases` is dropped first
+    let result = parser(syntax, &aliases)
+        .parse_recovery(Stream::from_iter(eoi, tokens[start..end].iter().cloned()));
+    result
 }
 
 #[cfg(test)]
@@ -324,8 +893,11 @@ mod tests {
     use super::ProgramLine;
     use super::Register;
     use super::Simple;
+    use super::Syntax;
     use super::Token;
+    use super::{lexer, Dialect};
     use super::{Opecode1, Opecode2, Opecode3, Opecode4, Opecode5, Opecode6, Opecode7, Opecode8};
+    use chumsky::Error;
 
     #[test]
     fn test_op1() {
@@ -407,6 +979,8 @@ mod tests {
                             rb: Register::Zero,
                             constant: 10,
                         }),
+                        span: 17..33,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -416,6 +990,8 @@ mod tests {
                             rb: Register::Two,
                             constant: 0x32,
                         }),
+                        span: 36..48,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -425,6 +1001,8 @@ mod tests {
                             rb: Register::Zero,
                             constant: 0,
                         }),
+                        span: 51..64,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -434,6 +1012,8 @@ mod tests {
                             rb: Register::Zero,
                             constant: 0o54,
                         }),
+                        span: 67..84,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -443,6 +1023,8 @@ mod tests {
                             rb: Register::Two,
                             constant: 0b01110101,
                         }),
+                        span: 87..105,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -452,6 +1034,8 @@ mod tests {
                             rb: Register::Zero,
                             constant: 0,
                         }),
+                        span: 108..122,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -461,6 +1045,8 @@ mod tests {
                             rb: Register::Two,
                             constant: 98,
                         }),
+                        span: 125..141,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -470,6 +1056,8 @@ mod tests {
                             rb: Register::Three,
                             constant: 0xBA,
                         }),
+                        span: 144..156,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -479,12 +1067,14 @@ mod tests {
                             rb: Register::Zero,
                             constant: 0,
                         }),
+                        span: 159..174,
+                        symbol_span: None,
                     },
                 ],
             }),
             Vec::<Simple<Token>>::new(),
         );
-        let actual = parse(input);
+        let actual = parse(input, Syntax::default());
         assert_eq!(expected, actual);
     }
 
@@ -522,6 +1112,8 @@ mod tests {
                             rb: Register::Zero,
                             constant: 12,
                         }),
+                        span: 17..26,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -530,6 +1122,8 @@ mod tests {
                             rb: Register::One,
                             constant: 0x34,
                         }),
+                        span: 29..42,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -538,12 +1132,14 @@ mod tests {
                             rb: Register::Two,
                             constant: 0o56,
                         }),
+                        span: 45..57,
+                        symbol_span: None,
                     },
                 ],
             }),
             Vec::<Simple<Token>>::new(),
         );
-        let actual = parse(input);
+        let actual = parse(input, Syntax::default());
         assert_eq!(expected, actual);
     }
 
@@ -590,6 +1186,8 @@ mod tests {
                             rb: Register::Zero,
                             constant: 12,
                         }),
+                        span: 17..28,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -598,6 +1196,8 @@ mod tests {
                             rb: Register::One,
                             constant: -0x34,
                         }),
+                        span: 31..44,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -606,6 +1206,8 @@ mod tests {
                             rb: Register::Two,
                             constant: 0o56,
                         }),
+                        span: 47..60,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -614,12 +1216,14 @@ mod tests {
                             rb: Register::Three,
                             constant: -0b0100101,
                         }),
+                        span: 65..85,
+                        symbol_span: None,
                     },
                 ],
             }),
             Vec::<Simple<Token>>::new(),
         );
-        let actual = parse(input);
+        let actual = parse(input, Syntax::default());
         assert_eq!(expected, actual);
     }
 
@@ -668,6 +1272,8 @@ mod tests {
                             rb: Register::One,
                             constant: 12,
                         }),
+                        span: 17..35,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -677,6 +1283,8 @@ mod tests {
                             rb: Register::Three,
                             constant: 0,
                         }),
+                        span: 38..50,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -686,12 +1294,14 @@ mod tests {
                             rb: Register::One,
                             constant: -0x34,
                         }),
+                        span: 53..73,
+                        symbol_span: None,
                     },
                 ],
             }),
             Vec::<Simple<Token>>::new(),
         );
-        let actual = parse(input);
+        let actual = parse(input, Syntax::default());
         assert_eq!(expected, actual);
     }
 
@@ -733,6 +1343,8 @@ mod tests {
                             rb: Register::Zero,
                             address: Address::Constant(0),
                         }),
+                        span: 17..24,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -744,6 +1356,8 @@ mod tests {
                                 offset: 12,
                             },
                         }),
+                        span: 27..45,
+                        symbol_span: Some(34..40),
                     },
                     ProgramLine {
                         label: None,
@@ -752,12 +1366,14 @@ mod tests {
                             rb: Register::Two,
                             address: Address::Constant(-34i16 as MachineAddress),
                         }),
+                        span: 48..61,
+                        symbol_span: None,
                     },
                 ],
             }),
             Vec::<Simple<Token>>::new(),
         );
-        let actual = parse(input);
+        let actual = parse(input, Syntax::default());
         assert_eq!(expected, actual);
     }
 
@@ -831,6 +1447,8 @@ mod tests {
                             op: Opecode6::Bdis,
                             address: Address::Constant(0),
                         }),
+                        span: 17..20,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -841,6 +1459,8 @@ mod tests {
                                 offset: 12,
                             },
                         }),
+                        span: 23..37,
+                        symbol_span: Some(26..32),
                     },
                     ProgramLine {
                         label: None,
@@ -848,6 +1468,8 @@ mod tests {
                             op: Opecode6::Bz,
                             address: Address::Constant(-34i16 as MachineAddress),
                         }),
+                        span: 40..49,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -858,6 +1480,8 @@ mod tests {
                                 offset: 0,
                             },
                         }),
+                        span: 52..61,
+                        symbol_span: Some(55..61),
                     },
                     ProgramLine {
                         label: None,
@@ -865,6 +1489,8 @@ mod tests {
                             op: Opecode6::Bc,
                             address: Address::Constant(56),
                         }),
+                        span: 64..73,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -875,6 +1501,8 @@ mod tests {
                                 offset: -78,
                             },
                         }),
+                        span: 76..91,
+                        symbol_span: Some(80..86),
                     },
                     ProgramLine {
                         label: None,
@@ -882,6 +1510,8 @@ mod tests {
                             op: Opecode6::Bnz,
                             address: Address::Constant(0),
                         }),
+                        span: 94..99,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -892,6 +1522,8 @@ mod tests {
                                 offset: 90,
                             },
                         }),
+                        span: 102..117,
+                        symbol_span: Some(106..112),
                     },
                     ProgramLine {
                         label: None,
@@ -899,6 +1531,8 @@ mod tests {
                             op: Opecode6::Bnc,
                             address: Address::Constant(-12i16 as MachineAddress),
                         }),
+                        span: 120..130,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -909,6 +1543,8 @@ mod tests {
                                 offset: 0,
                             },
                         }),
+                        span: 133..141,
+                        symbol_span: Some(135..141),
                     },
                     ProgramLine {
                         label: None,
@@ -916,6 +1552,8 @@ mod tests {
                             op: Opecode6::Bi,
                             address: Address::Constant(34),
                         }),
+                        span: 144..153,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -926,12 +1564,14 @@ mod tests {
                                 offset: -56,
                             },
                         }),
+                        span: 156..171,
+                        symbol_span: Some(160..166),
                     },
                 ],
             }),
             Vec::<Simple<Token>>::new(),
         );
-        let actual = parse(input);
+        let actual = parse(input, Syntax::default());
         assert_eq!(expected, actual);
     }
 
@@ -959,6 +1599,8 @@ mod tests {
                             op: Opecode7::Rio,
                             device: Device::Cr,
                         }),
+                        span: 17..20,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -966,12 +1608,14 @@ mod tests {
                             op: Opecode7::Wio,
                             device: Device::Lpt,
                         }),
+                        span: 23..30,
+                        symbol_span: None,
                     },
                 ],
             }),
             Vec::<Simple<Token>>::new(),
         );
-        let actual = parse(input);
+        let actual = parse(input, Syntax::default());
         assert_eq!(expected, actual);
     }
 
@@ -996,20 +1640,26 @@ mod tests {
                     ProgramLine {
                         label: None,
                         instruction: Some(Instruction::Group8 { op: Opecode8::Ret }),
+                        span: 17..20,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
                         instruction: Some(Instruction::Group8 { op: Opecode8::Nop }),
+                        span: 23..26,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
                         instruction: Some(Instruction::Group8 { op: Opecode8::Hlt }),
+                        span: 29..32,
+                        symbol_span: None,
                     },
                 ],
             }),
             Vec::<Simple<Token>>::new(),
         );
-        let actual = parse(input);
+        let actual = parse(input, Syntax::default());
         assert_eq!(expected, actual);
     }
 
@@ -1053,6 +1703,8 @@ mod tests {
                             value: 12,
                             unresolved_symbol: None,
                         }),
+                        span: 17..24,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -1060,6 +1712,8 @@ mod tests {
                             value: 0x6130,
                             unresolved_symbol: None,
                         }),
+                        span: 27..32,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -1067,28 +1721,163 @@ mod tests {
                             value: 0,
                             unresolved_symbol: Some("Label0"),
                         }),
+                        span: 35..44,
+                        symbol_span: Some(38..44),
                     },
                     ProgramLine {
                         label: None,
-                        instruction: Some(Instruction::Ds(10)),
+                        instruction: Some(Instruction::Ds(10, 0)),
+                        span: 47..51,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
                         instruction: Some(Instruction::Org(0x432)),
+                        span: 54..62,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
                         instruction: Some(Instruction::Org(0x89A)),
+                        span: 65..72,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
                         instruction: Some(Instruction::Org(0xA98)),
+                        span: 75..82,
+                        symbol_span: None,
+                    },
+                ],
+            }),
+            Vec::<Simple<Token>>::new(),
+        );
+        let actual = parse(input, Syntax::default());
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_data() {
+        let input = vec![
+            (Token::String("TITLE"), 0..5),
+            (Token::String("test_data"), 6..15),
+            (Token::Eol, 15..16),
+            (Token::String("S"), 16..17),
+            (Token::Colon, 17..18),
+            (Token::String("DCZ"), 19..22),
+            (Token::Text("Hi"), 23..27),
+            (Token::Comma, 27..28),
+            (Token::Decimal(1), 28..29),
+            (Token::Eol, 29..30),
+            (Token::String("DCP"), 30..33),
+            (Token::Text(r"A\x42C"), 34..42),
+            (Token::Comma, 42..43),
+            (Token::String("S"), 43..44),
+            (Token::Eol, 44..45),
+            (Token::String("END"), 45..48),
+        ];
+        let dc = |label, value, span| ProgramLine {
+            label,
+            instruction: Some(Instruction::Dc {
+                value,
+                unresolved_symbol: None,
+            }),
+            span,
+            symbol_span: None,
+        };
+        let expected = (
+            Some(Ast {
+                title: "test_data",
+                lines: vec![
+                    dc(Some("S"), 0x48, 16..29),
+                    dc(None, 0x69, 16..29),
+                    dc(None, 1, 16..29),
+                    dc(None, 0, 16..29),
+                    dc(None, 0x4142, 30..44),
+                    dc(None, 0x4300, 30..44),
+                    ProgramLine {
+                        label: None,
+                        instruction: Some(Instruction::Dc {
+                            value: 0,
+                            unresolved_symbol: Some("S"),
+                        }),
+                        span: 30..44,
+                        symbol_span: Some(43..44),
                     },
                 ],
             }),
             Vec::<Simple<Token>>::new(),
         );
-        let actual = parse(input);
+        let actual = parse(input, Syntax::default());
+        assert_eq!(expected, actual);
+
+        let input = vec![
+            (Token::String("TITLE"), 0..5),
+            (Token::String("test_data"), 6..15),
+            (Token::Eol, 15..16),
+            (Token::String("DC"), 16..18),
+            (Token::Text(""), 19..21),
+            (Token::Eol, 21..22),
+            (Token::String("END"), 22..25),
+        ];
+        let (_, errs) = parse(input, Syntax::default());
+        assert_eq!(
+            vec![Simple::custom(16..21, "DC defines no words").with_label("instruction")],
+            errs
+        );
+    }
+
+    #[test]
+    fn test_characters() {
+        let input = vec![
+            (Token::String("TITLE"), 0..5),
+            (Token::String("test_characters"), 6..21),
+            (Token::Eol, 21..22),
+            (Token::String("DC"), 22..24),
+            (Token::Char("A"), 25..28),
+            (Token::Comma, 28..29),
+            (Token::Char(r"\x41\n"), 29..37),
+            (Token::Comma, 37..38),
+            (Token::Chars("AB"), 38..41),
+            (Token::Eol, 41..42),
+            (Token::String("LC"), 42..44),
+            (Token::Decimal(0), 45..46),
+            (Token::Comma, 46..47),
+            (Token::Char("."), 47..50),
+            (Token::Eol, 50..51),
+            (Token::String("END"), 51..54),
+        ];
+        let dc = |value| ProgramLine {
+            label: None,
+            instruction: Some(Instruction::Dc {
+                value,
+                unresolved_symbol: None,
+            }),
+            span: 22..41,
+            symbol_span: None,
+        };
+        let expected = (
+            Some(Ast {
+                title: "test_characters",
+                lines: vec![
+                    dc(0x41),
+                    dc(0x410A),
+                    dc(0x4142),
+                    ProgramLine {
+                        label: None,
+                        instruction: Some(Instruction::Group2 {
+                            op: Opecode2::Lc,
+                            rb: Register::Zero,
+                            constant: b'.',
+                        }),
+                        span: 42..50,
+                        symbol_span: None,
+                    },
+                ],
+            }),
+            Vec::<Simple<Token>>::new(),
+        );
+        let actual = parse(input, Syntax::default());
         assert_eq!(expected, actual);
     }
 
@@ -1120,6 +1909,8 @@ mod tests {
                             op: Opecode7::Rio,
                             device: Device::Cr,
                         }),
+                        span: 17..31,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: Some("Label2"),
@@ -1127,12 +1918,14 @@ mod tests {
                             op: Opecode7::Wio,
                             device: Device::Lpt,
                         }),
+                        span: 34..50,
+                        symbol_span: None,
                     },
                 ],
             }),
             Vec::<Simple<Token>>::new(),
         );
-        let actual = parse(input);
+        let actual = parse(input, Syntax::default());
         assert_eq!(expected, actual);
     }
 
@@ -1172,6 +1965,8 @@ mod tests {
                             op: Opecode7::Rio,
                             device: Device::Cr,
                         }),
+                        span: 21..26,
+                        symbol_span: None,
                     },
                     ProgramLine {
                         label: None,
@@ -1179,15 +1974,312 @@ mod tests {
                             op: Opecode7::Wio,
                             device: Device::Lpt,
                         }),
+                        span: 33..40,
+                        symbol_span: None,
                     },
                 ],
             }),
             Vec::<Simple<Token>>::new(),
         );
-        let actual = parse(input);
+        let actual = parse(input, Syntax::default());
         assert_eq!(expected, actual);
     }
 
+    #[test]
+    fn test_case_insensitive() {
+        let source = "title T\nloop: add 0,1\n  Wio lpt\n  b loop\n  org 0x10\nEND\n";
+        let (ast, errs) = parse(lexer::tokenize(source), Syntax::default());
+        assert!(errs.is_empty());
+        let lines = ast.unwrap().lines;
+        assert_eq!(Some("loop"), lines[0].label);
+        assert_eq!(
+            vec![
+                Instruction::Group1 {
+                    op: Opecode1::Add,
+                    ra: Register::Zero,
+                    rb: Register::Zero,
+                    constant: 1,
+                },
+                Instruction::Group7 {
+                    op: Opecode7::Wio,
+                    device: Device::Lpt,
+                },
+                Instruction::Group6 {
+                    op: Opecode6::B,
+                    address: Address::Unresolved {
+                        symbol_name: "loop",
+                        offset: 0,
+                    },
+                },
+                Instruction::Org(0x10),
+            ],
+            lines
+                .into_iter()
+                .map(|line| line.instruction.unwrap())
+                .collect::<Vec<_>>()
+        );
+
+        let case_sensitive = Syntax {
+            case_sensitive: true,
+            ..Syntax::default()
+        };
+        assert!(!parse(lexer::tokenize(source), case_sensitive).1.is_empty());
+        let classic = Syntax {
+            dialect: Dialect::Classic,
+            ..Syntax::default()
+        };
+        assert!(!parse(lexer::tokenize(source), classic).1.is_empty());
+    }
+
+    #[test]
+    fn test_register_names() {
+        let source = "TITLE T\n  LEA SP,1(IX)\nSP REG 3\nIX REG 2\n  ADD r1,0(R2)\nEND\n";
+        let (ast, errs) = parse(lexer::tokenize(source), Syntax::default());
+        assert!(errs.is_empty());
+        assert_eq!(
+            vec![
+                Instruction::Group4 {
+                    op: Opecode4::Lea,
+                    ra: Register::Two,
+                    rb: Register::Three,
+                    constant: 1,
+                },
+                Instruction::Group1 {
+                    op: Opecode1::Add,
+                    ra: Register::Two,
+                    rb: Register::One,
+                    constant: 0,
+                },
+            ],
+            ast.unwrap()
+                .lines
+                .into_iter()
+                .map(|line| line.instruction.unwrap())
+                .collect::<Vec<_>>()
+        );
+
+        let source = "TITLE T\nSP REG 4\n  PUSH SP,1\nEND\n";
+        let (_, errs) = parse(lexer::tokenize(source), Syntax::default());
+        assert_eq!(
+            vec![
+                Simple::custom(
+                    8..16,
+                    "register alias `SP` names register 4, but registers are 0-3"
+                ),
+                Simple::custom(
+                    24..26,
+                    "register alias `SP` names register 4, but registers are 0-3"
+                )
+                .with_label("register 0-3"),
+            ],
+            errs
+        );
+
+        let classic = Syntax {
+            dialect: Dialect::Classic,
+            ..Syntax::default()
+        };
+        let source = "TITLE T\n  PUSH R1,1\nEND\n";
+        let (_, errs) = parse(lexer::tokenize(source), classic);
+        assert_eq!(
+            vec![Simple::custom(
+                15..17,
+                "register names are not allowed in the classic dialect"
+            )
+            .with_label("register 0-3")],
+            errs
+        );
+    }
+
+    #[test]
+    fn test_pseudo_instructions() {
+        let source = "TITLE T\nX: clr 1\n  INC 2\n  DEC 3\n  LI 0,0x12\n  LI 0,0x1200\n  LI 0,-1\n  JMP X\nEND\n";
+        let (ast, errs) = parse(lexer::tokenize(source), Syntax::default());
+        assert!(errs.is_empty());
+        let lines = ast.unwrap().lines;
+        assert_eq!(Some("X"), lines[0].label);
+        assert_eq!(None, lines[1].label);
+        let lc = |rb, constant| Instruction::Group2 {
+            op: Opecode2::Lc,
+            rb,
+            constant,
+        };
+        let sl = Instruction::Group3 {
+            op: Opecode3::Sl,
+            rb: Register::Zero,
+            constant: 8,
+        };
+        assert_eq!(
+            vec![
+                lc(Register::One, 0),
+                Instruction::Group1 {
+                    op: Opecode1::Add,
+                    ra: Register::Zero,
+                    rb: Register::Two,
+                    constant: 1,
+                },
+                Instruction::Group1 {
+                    op: Opecode1::Sub,
+                    ra: Register::Zero,
+                    rb: Register::Three,
+                    constant: 1,
+                },
+                lc(Register::Zero, 0x12),
+                lc(Register::Zero, 0x12),
+                sl.clone(),
+                lc(Register::Zero, 0xFF),
+                sl,
+                Instruction::Group1 {
+                    op: Opecode1::Or,
+                    ra: Register::Zero,
+                    rb: Register::Zero,
+                    constant: 0xFF,
+                },
+                Instruction::Group6 {
+                    op: Opecode6::Bi,
+                    address: Address::Constant(1),
+                },
+                Instruction::Dc {
+                    value: 0,
+                    unresolved_symbol: Some("X"),
+                },
+            ],
+            lines
+                .into_iter()
+                .map(|line| line.instruction.unwrap())
+                .collect::<Vec<_>>()
+        );
+
+        let classic = Syntax {
+            dialect: Dialect::Classic,
+            ..Syntax::default()
+        };
+        let source = "TITLE T\n  JMP 0x100\nEND\n";
+        let (_, errs) = parse(lexer::tokenize(source), classic);
+        assert_eq!(
+            vec![Simple::custom(
+                10..19,
+                "pseudo-instructions are not allowed in the classic dialect"
+            )
+            .with_label("instruction")],
+            errs
+        );
+    }
+
+    #[test]
+    fn test_far_jump() {
+        let source = "TITLE T\nX: JMP X+1\n  JMP X-2\n  JMP 0x100\nEND\n";
+        let (ast, errs) = parse(lexer::tokenize(source), Syntax::default());
+        assert!(errs.is_empty());
+        let targets: Vec<Instruction> = ast
+            .unwrap()
+            .lines
+            .into_iter()
+            .skip(1)
+            .step_by(2)
+            .map(|line| line.instruction.unwrap())
+            .collect();
+        assert_eq!(
+            vec![
+                Instruction::Dc {
+                    value: 1,
+                    unresolved_symbol: Some("X"),
+                },
+                Instruction::Dc {
+                    value: 0xFFFE,
+                    unresolved_symbol: Some("X"),
+                },
+                Instruction::Dc {
+                    value: 0x100,
+                    unresolved_symbol: None,
+                },
+            ],
+            targets
+        );
+        assert_eq!("DC X+1", targets[0].to_string());
+        assert_eq!("DC X-2", targets[1].to_string());
+
+        let source = "TITLE T\n  JMP *+1\nEND\n";
+        let (_, errs) = parse(lexer::tokenize(source), Syntax::default());
+        assert_eq!(
+            vec![
+                Simple::custom(14..17, "JMP needs a symbol or an absolute address, not `*`")
+                    .with_label("address")
+            ],
+            errs
+        );
+    }
+
+    #[test]
+    fn test_literals() {
+        let source = "TITLE T\n  L 1,=X\"1234\"\n  ST 2,=-1\n  LTORG\nEND\n";
+        let (ast, errs) = parse(lexer::tokenize(source), Syntax::default());
+        assert!(errs.is_empty());
+        assert_eq!(
+            vec![
+                Instruction::Group5 {
+                    op: Opecode5::L,
+                    rb: Register::One,
+                    address: Address::Literal(0x1234),
+                },
+                Instruction::Group5 {
+                    op: Opecode5::St,
+                    rb: Register::Two,
+                    address: Address::Literal(0xFFFF),
+                },
+                Instruction::Ltorg,
+            ],
+            ast.unwrap()
+                .lines
+                .into_iter()
+                .map(|line| line.instruction.unwrap())
+                .collect::<Vec<_>>()
+        );
+
+        let classic = Syntax {
+            dialect: Dialect::Classic,
+            ..Syntax::default()
+        };
+        let (_, errs) = parse(lexer::tokenize(source), classic);
+        assert_eq!(1, errs.len());
+        assert_eq!(
+            Some("literals are not allowed in the classic dialect"),
+            match errs[0].reason() {
+                chumsky::error::SimpleReason::Custom(message) => Some(message.as_str()),
+                _ => None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_layout() {
+        let source = "TITLE T\n  DS 2,-1\n  FILL 3,X\"AB\"\n  ALIGN 8\n  RESERVE 100\nEND\n";
+        let (ast, errs) = parse(lexer::tokenize(source), Syntax::default());
+        assert!(errs.is_empty());
+        assert_eq!(
+            vec![
+                Instruction::Ds(2, 0xFFFF),
+                Instruction::Ds(3, 0xAB),
+                Instruction::Align(8),
+                Instruction::Reserve(100),
+            ],
+            ast.unwrap()
+                .lines
+                .into_iter()
+                .map(|line| line.instruction.unwrap())
+                .collect::<Vec<_>>()
+        );
+
+        let (_, errs) = parse(
+            lexer::tokenize("TITLE T\n  ALIGN 0\nEND\n"),
+            Syntax::default(),
+        );
+        assert_eq!(
+            vec![Simple::custom(10..17, "alignment must be 1 or more").with_label("instruction")],
+            errs
+        );
+    }
+
     #[test]
     fn test_invalid_opecode() {
         let input = vec![
@@ -1203,7 +2295,7 @@ mod tests {
             (Token::String("END"), 32..35),
         ];
         let expected = None;
-        let actual = parse(input);
+        let actual = parse(input, Syntax::default());
         assert_eq!(expected, actual.0);
         assert!(!actual.1.is_empty());
     }
@@ -1223,7 +2315,7 @@ mod tests {
             (Token::String("END"), 32..35),
         ];
         // let expected = None;
-        let actual = parse(input);
+        let actual = parse(input, Syntax::default());
         // assert_eq!(expected, actual.0);
         assert!(!actual.1.is_empty());
     }
@@ -0,0 +1,372 @@
+// Expansion of repeat blocks before parsing.
+//
+//         REPT  4,I           IRP   R,<1,2,3>
+//         DC    I             CLR   R
+//         ENDR                ENDR
+//
+// The lines up to ENDR are repeated, with the counter symbol replaced by 0, 1, ... or by each
+// argument. Expanded tokens keep the spans of the body, so errors point into it.
+
+use crate::dialect::Feature;
+use crate::parser::Syntax;
+use crate::token::Token;
+
+use chumsky::error::Simple;
+use logos::Span;
+
+type Line<'a> = Vec<(Token<'a>, Span)>;
+
+// A block header, with the tokens which replace the counter symbol in each repetition
+struct Block<'a> {
+    symbol: Option<&'a str>,
+    values: Vec<Line<'a>>,
+}
+
+// Whether the mnemonic of the line is one of `keywords`. `REPT:` is a label, not a keyword.
+fn is_block_keyword(line: &[(Token, Span)], syntax: Syntax, keywords: &[&str]) -> bool {
+    let instruction = match line {
+        [(Token::String(_), _), (Token::Colon, _), rest @ ..] => rest,
+        _ => line,
+    };
+    match instruction {
+        [(Token::String(_), _), (Token::Colon, _), ..] => false,
+        [(Token::String(head), _), ..] => keywords
+            .iter()
+            .any(|keyword| syntax.is_keyword(head, keyword)),
+        _ => false,
+    }
+}
+
+fn is_header(line: &[(Token, Span)], syntax: Syntax) -> bool {
+    is_block_keyword(line, syntax, &["REPT", "IRP"])
+}
+
+fn is_end(line: &[(Token, Span)], syntax: Syntax) -> bool {
+    is_block_keyword(line, syntax, &["ENDR"])
+}
+
+// Span of a line without its end
+fn span_of(line: &[(Token, Span)]) -> Span {
+    let line = match line.last() {
+        Some((Token::Eol, _)) => &line[..line.len() - 1],
+        _ => line,
+    };
+    match (line.first(), line.last()) {
+        (Some((_, first)), Some((_, last))) => first.start..last.end,
+        _ => 0..0,
+    }
+}
+
+// Reads `REPT n[,sym]` or `IRP sym,<a,b,...>`
+fn header<'a>(line: &[(Token<'a>, Span)], syntax: Syntax) -> Result<Block<'a>, String> {
+    let error = |message: &str| Err(message.to_string());
+    if !syntax.dialect.allows(Feature::RepeatBlock) {
+        return Err(syntax.dialect.disallowed(Feature::RepeatBlock));
+    }
+
+    let (head, operands) = match line {
+        [(Token::String(head), _), operands @ ..] => (head, operands),
+        _ => return error("expected REPT or IRP"),
+    };
+    if syntax.is_keyword(head, "REPT") {
+        let (count, symbol) = match operands {
+            [(count, _)] => (count, None),
+            [(count, _), (Token::Comma, _), (Token::String(symbol), _)] => (count, Some(*symbol)),
+            _ => return error("expected `REPT count` or `REPT count,symbol`"),
+        };
+        let count = match count {
+            Token::Decimal(n) | Token::Hexadecimal(n) | Token::Octal(n) | Token::Binary(n) => *n,
+            _ => return error("expected a count after REPT"),
+        };
+        Ok(Block {
+            symbol,
+            values: (0..count)
+                .map(|i| vec![(Token::Decimal(i), 0..0)])
+                .collect(),
+        })
+    } else {
+        let usage = "expected `IRP symbol,<argument,...>`";
+        let [(Token::String(symbol), _), (Token::Comma, _), (Token::Langle, _), rest @ ..] =
+            operands
+        else {
+            return error(usage);
+        };
+        let Some(((Token::Rangle, _), arguments)) = rest.split_last() else {
+            return error(usage);
+        };
+        let values = if arguments.is_empty() {
+            Vec::new()
+        } else {
+            arguments
+                .split(|(token, _)| *token == Token::Comma)
+                .map(|argument| argument.to_vec())
+                .collect()
+        };
+        if values.iter().any(|value| value.is_empty()) {
+            return error("an argument of IRP is empty");
+        }
+        Ok(Block {
+            symbol: Some(*symbol),
+            values,
+        })
+    }
+}
+
+// Replaces the counter symbol with `value`, which takes the span of the symbol
+fn substitute<'a>(line: &Line<'a>, symbol: Option<&str>, value: &Line<'a>) -> Line<'a> {
+    line.iter()
+        .flat_map(|(token, span)| match token {
+            Token::String(s) if Some(*s) == symbol => value
+                .iter()
+                .map(|(token, _)| (token.clone(), span.clone()))
+                .collect(),
+            _ => vec![(token.clone(), span.clone())],
+        })
+        .collect()
+}
+
+fn expand_lines<'a>(
+    lines: &[Line<'a>],
+    syntax: Syntax,
+    errors: &mut Vec<Simple<Token<'a>>>,
+) -> Vec<(Token<'a>, Span)> {
+    let mut expanded = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = &lines[i];
+        if is_end(line, syntax) {
+            errors.push(Simple::custom(span_of(line), "ENDR without REPT or IRP"));
+            i += 1;
+            continue;
+        }
+        if !is_header(line, syntax) {
+            expanded.extend(line.iter().cloned());
+            i += 1;
+            continue;
+        }
+
+        // Finds the ENDR of the block, skipping nested ones
+        let mut depth = 0;
+        let end = (i + 1..lines.len()).find(|j| {
+            if is_header(&lines[*j], syntax) {
+                depth += 1;
+            } else if is_end(&lines[*j], syntax) {
+                if depth == 0 {
+                    return true;
+                }
+                depth -= 1;
+            }
+            false
+        });
+        let Some(end) = end else {
+            errors.push(Simple::custom(
+                span_of(line),
+                "the block is not closed by ENDR",
+            ));
+            i += 1;
+            continue;
+        };
+        // Operands end before the end of line
+        let operands = match line.last() {
+            Some((Token::Eol, _)) => &line[..line.len() - 1],
+            _ => &line[..],
+        };
+        // A label on the block goes to its first line
+        let (label, operands) = match operands {
+            [label @ (Token::String(_), _), colon @ (Token::Colon, _), rest @ ..] => {
+                (Some([label.clone(), colon.clone()]), rest)
+            }
+            _ => (None, operands),
+        };
+        // A label in the body would be defined on every repetition. Nested blocks are expanded
+        // once for each repetition of the outer one, so each label is reported once.
+        for body_line in &lines[i + 1..end] {
+            if let [(Token::String(label), first), (Token::Colon, last), ..] = &body_line[..] {
+                let error = Simple::custom(
+                    first.start..last.end,
+                    format!("label `{label}` would be defined on every repetition of the block"),
+                );
+                if !errors.contains(&error) {
+                    errors.push(error);
+                }
+            }
+        }
+        match header(operands, syntax) {
+            Ok(block) => {
+                let mut repeated = Vec::new();
+                for value in &block.values {
+                    let body: Vec<Line> = lines[i + 1..end]
+                        .iter()
+                        .map(|line| substitute(line, block.symbol, value))
+                        .collect();
+                    repeated.extend(expand_lines(&body, syntax, errors));
+                }
+                if let Some(label) = label {
+                    let first = repeated.iter().position(|(token, _)| *token != Token::Eol);
+                    let labelled = matches!(
+                        first.map(|first| &repeated[first..]),
+                        Some([_, (Token::Colon, _), ..])
+                    );
+                    match first {
+                        Some(first) if !labelled => {
+                            repeated.splice(first..first, label);
+                        }
+                        _ => errors.push(Simple::custom(
+                            span_of(line),
+                            "the label of the block needs a first line without a label",
+                        )),
+                    }
+                }
+                expanded.extend(repeated);
+            }
+            Err(message) => errors.push(Simple::custom(span_of(line), message)),
+        }
+        i = end + 1;
+    }
+    expanded
+}
+
+// Expands REPT and IRP blocks in `tokens`
+pub fn expand(
+    tokens: Vec<(Token, Span)>,
+    syntax: Syntax,
+) -> (Vec<(Token, Span)>, Vec<Simple<Token>>) {
+    let mut lines = Vec::new();
+    let mut line = Vec::new();
+    for (token, span) in tokens {
+        let is_eol = token == Token::Eol;
+        line.push((token, span));
+        if is_eol {
+            lines.push(std::mem::take(&mut line));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    let mut errors = Vec::new();
+    let expanded = expand_lines(&lines, syntax, &mut errors);
+    (expanded, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand;
+    use crate::dialect::Dialect;
+    use crate::lexer::tokenize;
+    use crate::parser::Syntax;
+    use crate::token::Token;
+
+    use chumsky::error::Simple;
+
+    fn expanded(source: &str) -> String {
+        let (tokens, errors) = expand(tokenize(source), Syntax::default());
+        assert!(errors.is_empty());
+        tokens
+            .iter()
+            .map(|(token, _)| match token {
+                Token::Eol => "\n".to_string(),
+                token => format!("{token} "),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rept() {
+        assert_eq!(
+            "HLT \nDC 0 \nDC 1 \nDC 2 \nEND ",
+            expanded("HLT\nREPT 3,I\nDC I\nENDR\nEND")
+        );
+        assert_eq!("NOP \nNOP \n", expanded("rept 2\nNOP\nendr\n"));
+        assert_eq!("", expanded("REPT 0\nNOP\nENDR\n"));
+    }
+
+    #[test]
+    fn test_irp() {
+        assert_eq!(
+            "CLR 1 \nCLR 2 \nL 0 , X\"1 \n",
+            expanded("IRP R,<1,2>\nCLR R\nENDR\nIRP A,<X\"1>\nL 0,A\nENDR\n")
+        );
+        assert_eq!("", expanded("IRP R,<>\nCLR R\nENDR\n"));
+    }
+
+    #[test]
+    fn test_label() {
+        assert_eq!(
+            "L : DC 1 \nDC 2 \n",
+            expanded("L: IRP V,<1,2>\nDC V\nENDR\n")
+        );
+    }
+
+    #[test]
+    fn test_keyword_as_label() {
+        assert_eq!(
+            "REPT : \nENDR : \nL : IRP : NOP \n",
+            expanded("REPT:\nENDR:\nL: IRP: NOP\n")
+        );
+        assert_eq!(
+            vec![Simple::custom(6..10, "ENDR without REPT or IRP")],
+            errors("REPT:\nENDR\n", Syntax::default())
+        );
+    }
+
+    #[test]
+    fn test_nested() {
+        assert_eq!(
+            "DC 0 \nDC 0 \nDC 1 \nDC 1 \n",
+            expanded("REPT 2,I\nIRP J,<I,I>\nDC J\nENDR\nENDR\n")
+        );
+    }
+
+    fn errors(source: &str, syntax: Syntax) -> Vec<Simple<Token<'_>>> {
+        expand(tokenize(source), syntax).1
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            vec![Simple::custom(0..6, "the block is not closed by ENDR")],
+            errors("REPT 2\nNOP\n", Syntax::default())
+        );
+        assert_eq!(
+            vec![Simple::custom(4..8, "ENDR without REPT or IRP")],
+            errors("NOP\nENDR\n", Syntax::default())
+        );
+        assert_eq!(
+            vec![Simple::custom(0..9, "expected `IRP symbol,<argument,...>`")],
+            errors("IRP R,1,2\nENDR\n", Syntax::default())
+        );
+        assert_eq!(
+            vec![Simple::custom(
+                0..9,
+                "the label of the block needs a first line without a label"
+            )],
+            errors("L: REPT 2\nENDR\n", Syntax::default())
+        );
+        assert_eq!(
+            vec![Simple::custom(
+                15..17,
+                "label `L` would be defined on every repetition of the block"
+            )],
+            errors("REPT 2\n  NOP\n  L: NOP\n  B L\nENDR\n", Syntax::default())
+        );
+        assert_eq!(
+            vec![Simple::custom(
+                14..16,
+                "label `L` would be defined on every repetition of the block"
+            )],
+            errors("REPT 2\nREPT 2\nL: NOP\nENDR\nENDR\n", Syntax::default())
+        );
+        let classic = Syntax {
+            dialect: Dialect::Classic,
+            ..Syntax::default()
+        };
+        assert_eq!(
+            vec![Simple::custom(
+                0..6,
+                "REPT and IRP are not allowed in the classic dialect"
+            )],
+            errors("REPT 2\nENDR\n", classic)
+        );
+    }
+}
@@ -1,11 +1,21 @@
-use crate::instruction::{Address, Instruction, MachineAddress};
+use crate::instruction::{Address, Instruction, MachineAddress, Opecode6};
 use crate::parser;
 
+use logos::Span;
+
 use std::collections::{HashMap, HashSet};
 
-type SymbolTable<'a> = HashMap<&'a str, MachineAddress>;
+pub type SymbolTable<'a> = HashMap<&'a str, MachineAddress>;
+
+// A use of `symbol` at `span` by the instruction of `lines[line]`
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Reference<'a> {
+    pub symbol: &'a str,
+    pub line: usize,
+    pub span: Span,
+}
 
-fn create_symbol_table<'a>(lines: &'a Vec<parser::ProgramLine>) -> SymbolTable<'a> {
+pub fn create_symbol_table<'a>(lines: &'a [parser::ProgramLine]) -> SymbolTable<'a> {
     let mut symbol_table = SymbolTable::new();
 
     let mut address: MachineAddress = 0;
@@ -21,27 +31,290 @@ fn create_symbol_table<'a>(lines: &'a Vec<parser::ProgramLine>) -> SymbolTable<'
                 }
             }
 
-            match instruction {
-                Instruction::Org(_) => {}
-                Instruction::Ds(s) => {
-                    address += s;
-                }
-                _ => {
-                    address += 1;
-                }
-            }
+            address = address.wrapping_add(instruction.size(address));
         }
     }
 
     symbol_table
 }
 
+// Collects uses of symbols by Group5/Group6 addresses and DC, before they are resolved
+pub fn collect_references<'a>(lines: &[parser::ProgramLine<'a>]) -> Vec<Reference<'a>> {
+    let mut references = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        let symbol = match &line.instruction {
+            Some(Instruction::Group5 {
+                address: Address::Unresolved { symbol_name, .. },
+                ..
+            }) => symbol_name,
+            Some(Instruction::Group6 {
+                address: Address::Unresolved { symbol_name, .. },
+                ..
+            }) => symbol_name,
+            Some(Instruction::Dc {
+                unresolved_symbol: Some(symbol_name),
+                ..
+            }) => symbol_name,
+            _ => continue,
+        };
+        references.push(Reference {
+            symbol,
+            line: i,
+            span: line
+                .symbol_span
+                .clone()
+                .unwrap_or_else(|| line.span.clone()),
+        });
+    }
+
+    references
+}
+
+// Finds the line which defines each symbol; the first definition wins as in `create_symbol_table`
+pub fn definitions<'a>(lines: &[parser::ProgramLine<'a>]) -> HashMap<&'a str, usize> {
+    let mut definitions = HashMap::new();
+    for (i, line) in lines.iter().enumerate() {
+        if let (Some(label), Some(_)) = (line.label, &line.instruction) {
+            definitions.entry(label).or_insert(i);
+        }
+    }
+    definitions
+}
+
+// Address of the instruction of each line
+fn addresses(lines: &[parser::ProgramLine]) -> Vec<MachineAddress> {
+    let mut address: MachineAddress = 0;
+    lines
+        .iter()
+        .map(|line| {
+            if let Some(Instruction::Org(a)) = &line.instruction {
+                address = *a;
+            }
+            let current_address = address;
+            if let Some(instruction) = &line.instruction {
+                address = address.wrapping_add(instruction.size(address));
+            }
+            current_address
+        })
+        .collect()
+}
+
+// Finds Group6 branches to symbols beyond the reach of the 8-bit displacement
+pub fn out_of_range_branches(lines: &[parser::ProgramLine]) -> Vec<usize> {
+    let symbol_table = create_symbol_table(lines);
+    addresses(lines)
+        .into_iter()
+        .enumerate()
+        .filter(|(i, current_address)| match &lines[*i].instruction {
+            Some(Instruction::Group6 {
+                address:
+                    Address::Unresolved {
+                        symbol_name,
+                        offset,
+                    },
+                ..
+            }) => symbol_table.get(symbol_name).is_some_and(|a| {
+                let displacement = a
+                    .wrapping_add(*offset as u16)
+                    .wrapping_sub(*current_address);
+                i8::try_from(displacement as i16).is_err()
+            }),
+            _ => false,
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+pub fn has_long_form(op: &Opecode6) -> bool {
+    !matches!(op, Opecode6::Bdis | Opecode6::Bi | Opecode6::Bsr)
+}
+
+// A branch which reaches any address: the inverted condition skips `BI *+1`, which branches
+// through the word holding the target. BSR, BI and BDIS have no long form.
+fn long_branch<'a>(
+    op: &Opecode6,
+    symbol_name: &'a str,
+    offset: i16,
+) -> Option<Vec<Instruction<'a>>> {
+    if !has_long_form(op) {
+        return None;
+    }
+    let inverse = match op {
+        Opecode6::Bp => Some(Opecode6::Bnp),
+        Opecode6::Bz => Some(Opecode6::Bnz),
+        Opecode6::Bm => Some(Opecode6::Bnm),
+        Opecode6::Bc => Some(Opecode6::Bnc),
+        Opecode6::Bnp => Some(Opecode6::Bp),
+        Opecode6::Bnz => Some(Opecode6::Bz),
+        Opecode6::Bnm => Some(Opecode6::Bm),
+        Opecode6::Bnc => Some(Opecode6::Bc),
+        _ => None,
+    };
+    let mut words: Vec<Instruction> = inverse
+        .into_iter()
+        .map(|op| Instruction::Group6 {
+            op,
+            address: Address::Constant(3),
+        })
+        .collect();
+    words.push(Instruction::Group6 {
+        op: Opecode6::Bi,
+        address: Address::Constant(1),
+    });
+    words.push(Instruction::Dc {
+        value: offset as u16,
+        unresolved_symbol: Some(symbol_name),
+    });
+    Some(words)
+}
+
+// Rewrites branches out of range into long forms, until no more branches go out of range as
+// the code grows. Returns the spans of the rewritten branches.
+pub fn relax_branches(lines: &mut Vec<parser::ProgramLine>) -> Vec<Span> {
+    let mut relaxed = Vec::new();
+    loop {
+        let mut changed = false;
+        // From the end, so that the indices of the remaining branches do not move
+        for i in out_of_range_branches(lines).into_iter().rev() {
+            let words = match &lines[i].instruction {
+                Some(Instruction::Group6 {
+                    op,
+                    address:
+                        Address::Unresolved {
+                            symbol_name,
+                            offset,
+                        },
+                }) => long_branch(op, symbol_name, *offset),
+                _ => None,
+            };
+            let Some(words) = words else {
+                continue;
+            };
+            let line = lines.remove(i);
+            let expansion: Vec<parser::ProgramLine> = words
+                .into_iter()
+                .enumerate()
+                .map(|(j, instruction)| parser::ProgramLine {
+                    label: line.label.filter(|_| j == 0),
+                    // The symbol moves to the DC word which holds the target
+                    symbol_span: line.symbol_span.clone().filter(|_| {
+                        matches!(
+                            instruction,
+                            Instruction::Dc {
+                                unresolved_symbol: Some(_),
+                                ..
+                            }
+                        )
+                    }),
+                    instruction: Some(instruction),
+                    span: line.span.clone(),
+                })
+                .collect();
+            lines.splice(i..i, expansion);
+            relaxed.push(line.span);
+            changed = true;
+        }
+        if !changed {
+            break;
+        }
+    }
+    relaxed.sort_by_key(|span| span.start);
+    relaxed
+}
+
+// Places the words of literals after each LTORG and at `end`. A pool holds each value used since
+// the previous pool once.
+pub fn place_literals(lines: &mut Vec<parser::ProgramLine>, end: Span) {
+    let pool = |values: &mut Vec<u16>, span: &Span| {
+        values
+            .drain(..)
+            .map(|value| parser::ProgramLine {
+                label: None,
+                instruction: Some(Instruction::Dc {
+                    value,
+                    unresolved_symbol: None,
+                }),
+                span: span.clone(),
+                symbol_span: None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let mut placed = Vec::new();
+    let mut values = Vec::new();
+    for line in lines.drain(..) {
+        match &line.instruction {
+            Some(Instruction::Group5 {
+                address: Address::Literal(value),
+                ..
+            }) if !values.contains(value) => values.push(*value),
+            Some(Instruction::Ltorg) => {
+                let span = line.span.clone();
+                placed.push(line);
+                placed.extend(pool(&mut values, &span));
+                continue;
+            }
+            _ => {}
+        }
+        placed.push(line);
+    }
+    if !values.is_empty() {
+        placed.push(parser::ProgramLine {
+            label: None,
+            instruction: Some(Instruction::Ltorg),
+            span: end.clone(),
+            symbol_span: None,
+        });
+        placed.extend(pool(&mut values, &end));
+    }
+    *lines = placed;
+}
+
+// Finds literals whose words are placed beyond the reach of the 8-bit displacement
+pub fn out_of_range_literals(lines: &[parser::ProgramLine]) -> Vec<usize> {
+    let addresses = addresses(lines);
+    (0..lines.len())
+        .filter(|i| match &lines[*i].instruction {
+            Some(Instruction::Group5 {
+                address: Address::Literal(value),
+                ..
+            }) => literal_address(lines, &addresses, *i, *value).is_some_and(|a| {
+                let displacement = a.wrapping_sub(addresses[*i]);
+                i8::try_from(displacement as i16).is_err()
+            }),
+            _ => false,
+        })
+        .collect()
+}
+
+// Address of the word of `value` in the first pool after `lines[line]`
+fn literal_address(
+    lines: &[parser::ProgramLine],
+    addresses: &[MachineAddress],
+    line: usize,
+    value: u16,
+) -> Option<MachineAddress> {
+    let ltorg = (line..lines.len()).find(|i| lines[*i].instruction == Some(Instruction::Ltorg))?;
+    (ltorg + 1..lines.len())
+        .take_while(|i| lines[*i].span == lines[ltorg].span)
+        .find(|i| {
+            lines[*i].instruction
+                == Some(Instruction::Dc {
+                    value,
+                    unresolved_symbol: None,
+                })
+        })
+        .map(|i| addresses[i])
+}
+
 pub fn resolve_symbols(lines: &mut Vec<parser::ProgramLine>) {
     let tmp: Vec<parser::ProgramLine> = (*lines).clone();
     let symbol_table = create_symbol_table(&tmp);
+    let addresses = addresses(&tmp);
     let mut current_address: MachineAddress = 0;
 
-    for line in lines {
+    for (i, line) in lines.iter_mut().enumerate() {
         if let Some(instruction) = &mut line.instruction {
             if let Instruction::Org(a) = instruction {
                 current_address = *a;
@@ -64,6 +337,10 @@ pub fn resolve_symbols(lines: &mut Vec<parser::ProgramLine>) {
                                 a.wrapping_sub(current_address).wrapping_add(*offset as u16),
                             );
                         }
+                    } else if let Address::Literal(value) = address {
+                        if let Some(a) = literal_address(&tmp, &addresses, i, *value) {
+                            *address = Address::Constant(a.wrapping_sub(current_address));
+                        }
                     }
                 }
                 Instruction::Group6 { op: _, address } => {
@@ -87,7 +364,8 @@ pub fn resolve_symbols(lines: &mut Vec<parser::ProgramLine>) {
                     if let Some(symbol_name) = unresolved_symbol {
                         let wrapped_address = symbol_table.get(symbol_name);
                         if let Some(a) = wrapped_address {
-                            *value = *a;
+                            // The value of a word holding a symbol is the offset from it
+                            *value = a.wrapping_add(*value);
                             *unresolved_symbol = None;
                         }
                     }
@@ -95,11 +373,7 @@ pub fn resolve_symbols(lines: &mut Vec<parser::ProgramLine>) {
                 _ => {}
             }
 
-            match &instruction {
-                Instruction::Org(_) => {}
-                Instruction::Ds(s) => current_address += s,
-                _ => current_address += 1,
-            }
+            current_address = current_address.wrapping_add(instruction.size(current_address));
         }
     }
 }
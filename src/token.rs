@@ -1,20 +1,29 @@
+use crate::dialect::Feature;
 use crate::instruction::MachineAddress;
 
 use logos::Logos;
 
+use std::fmt;
+
 #[derive(Clone, Debug, Eq, Hash, Logos, PartialEq)]
 pub enum Token<'a> {
-    #[regex("[a-zA-Z][a-zA-Z0-9]*", |lex| lex.slice())]
+    #[regex("[a-zA-Z][a-zA-Z0-9_]*", |lex| lex.slice())]
     String(&'a str),
-    #[regex(r"0|[1-9][0-9]*", |lex| lex.slice().parse())]
+    #[regex(r"0|[1-9][0-9_]*", |lex| number(lex.slice(), 10))]
     Decimal(u16),
     #[regex(r"[0-9]+[a-fA-F][a-fA-F0-9]+", |lex| u16::from_str_radix(lex.slice(), 16))]
     NoPrefixHexadecimal(MachineAddress), // This is only used by ORG instruction
-    #[regex(r#"X"[a-fA-F0-9]+"#, |lex| u16::from_str_radix(&lex.slice()[2..], 16))]
+    // The closing quote of `X"1F"` is optional
+    #[regex(r#"X"[a-fA-F0-9]+"?"#, |lex| u16::from_str_radix(lex.slice()[2..].trim_end_matches('"'), 16))]
+    #[regex(r"0[xX][0-9a-fA-F_]+", |lex| number(&lex.slice()[2..], 16))]
+    #[regex(r"\$[0-9a-fA-F_]+", |lex| number(&lex.slice()[1..], 16))]
+    #[regex(r"[0-9][0-9a-fA-F_]*[hH]", |lex| { let s = lex.slice(); number(&s[..s.len() - 1], 16) })]
     Hexadecimal(u16),
-    #[regex(r#"O"[0-7]+"#, |lex| u16::from_str_radix(&lex.slice()[2..], 8))]
+    #[regex(r#"O"[0-7]+"?"#, |lex| u16::from_str_radix(lex.slice()[2..].trim_end_matches('"'), 8))]
+    #[regex(r"0[oO][0-7_]+", |lex| number(&lex.slice()[2..], 8))]
     Octal(u16),
-    #[regex(r#"B"[01]+"#, |lex| u16::from_str_radix(&lex.slice()[2..], 2))]
+    #[regex(r#"B"[01]+"?"#, |lex| u16::from_str_radix(lex.slice()[2..].trim_end_matches('"'), 2))]
+    #[regex(r"0[bB][01_]+", |lex| number(&lex.slice()[2..], 2), priority = 10)]
     Binary(u16),
     #[token("(")]
     Lparen,
@@ -22,8 +31,13 @@ pub enum Token<'a> {
     Rparen,
     #[token("*")]
     Star,
-    #[regex("'..", |lex| &lex.slice()[1..=2])]
-    Chars(&'a str),
+    #[regex(r"'([\x00-\t\x0B-\x7F]|[\u{80}-\u{10FFFF}])([\x00-\t\x0B-\x7F]|[\u{80}-\u{10FFFF}])", |lex| { let s = lex.slice(); &s[1..] })]
+    Chars(&'a str), // Two characters without escapes or a closing quote
+    // One or two characters with escapes, closed by a quote
+    #[regex(r#"'([\x00-\t\x0B-&(-\[\]-\x7F]|[\u{80}-\u{10FFFF}]|\\[nrt0\\'"]|\\x[0-9a-fA-F][0-9a-fA-F])([\x00-\t\x0B-&(-\[\]-\x7F]|[\u{80}-\u{10FFFF}]|\\[nrt0\\'"]|\\x[0-9a-fA-F][0-9a-fA-F])?'"#, |lex| { let s = lex.slice(); &s[1..s.len() - 1] }, priority = 10)]
+    Char(&'a str),
+    #[regex(r#""([^"\\\n]|\\[^\n])*""#, |lex| { let s = lex.slice(); &s[1..s.len() - 1] })]
+    Text(&'a str),
     #[token("+")]
     Plus,
     #[token("-")]
@@ -32,9 +46,153 @@ pub enum Token<'a> {
     Comma,
     #[token(":")]
     Colon,
+    #[token("=")]
+    Equal,
+    #[token("<")]
+    Langle,
+    #[token(">")]
+    Rangle,
     #[token("\n")]
     Eol,
     #[regex(r"[ \r\t\f]+|;.*", |_| logos::Skip)]
     #[error]
     Error,
 }
+
+// Reads digits which may be separated by `_`
+fn number(digits: &str, radix: u32) -> Option<u16> {
+    let digits = digits.replace('_', "");
+    u16::from_str_radix(&digits, radix).ok()
+}
+
+impl Token<'_> {
+    // The feature of a dialect which the token needs, as it is written in `text`
+    pub fn feature(&self, text: &str) -> Option<Feature> {
+        match self {
+            Token::String(s) if s.contains('_') => Some(Feature::UnderscoreSymbol),
+            Token::Decimal(_) | Token::Hexadecimal(_) | Token::Octal(_) | Token::Binary(_)
+                if text.contains('_') =>
+            {
+                Some(Feature::DigitSeparator)
+            }
+            Token::Hexadecimal(_) | Token::Octal(_) | Token::Binary(_) if !text.contains('"') => {
+                Some(Feature::PrefixedNumber)
+            }
+            Token::NoPrefixHexadecimal(_) => Some(Feature::BareHexadecimal),
+            Token::Chars(_) => Some(Feature::LegacyCharacters),
+            Token::Char(_) => Some(Feature::CharacterLiteral),
+            Token::Text(_) => Some(Feature::StringLiteral),
+            Token::Equal => Some(Feature::LiteralPool),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::String(s) => write!(f, "{s}"),
+            Token::Decimal(d) => write!(f, "{d}"),
+            Token::NoPrefixHexadecimal(h) => write!(f, "{h:X}"),
+            Token::Hexadecimal(h) => write!(f, "X\"{h:X}"),
+            Token::Octal(o) => write!(f, "O\"{o:o}"),
+            Token::Binary(b) => write!(f, "B\"{b:b}"),
+            Token::Lparen => write!(f, "("),
+            Token::Rparen => write!(f, ")"),
+            Token::Star => write!(f, "*"),
+            Token::Chars(s) => write!(f, "'{s}"),
+            Token::Char(s) => write!(f, "'{s}'"),
+            Token::Text(s) => write!(f, "\"{s}\""),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Comma => write!(f, ","),
+            Token::Colon => write!(f, ":"),
+            Token::Equal => write!(f, "="),
+            Token::Langle => write!(f, "<"),
+            Token::Rangle => write!(f, ">"),
+            Token::Eol => write!(f, "end of line"),
+            Token::Error => write!(f, "invalid token"),
+        }
+    }
+}
+
+// Decodes escape sequences of a string or character literal into 8-bit characters
+pub fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        let c = if c == '\\' {
+            match chars.next() {
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                Some('0') => '\0',
+                Some(c @ ('\\' | '"' | '\'')) => c,
+                Some('x') => {
+                    let digits: String = chars.by_ref().take(2).collect();
+                    match u8::from_str_radix(&digits, 16) {
+                        Ok(byte) if digits.len() == 2 => byte as char,
+                        _ => return Err(format!("`\\x{digits}` needs two hexadecimal digits")),
+                    }
+                }
+                Some(c) => return Err(format!("unknown escape sequence `\\{c}`")),
+                None => return Err("escape sequence is not finished".to_string()),
+            }
+        } else {
+            c
+        };
+        match u8::try_from(c) {
+            Ok(byte) => bytes.push(byte),
+            Err(_) => return Err(format!("character `{c}` does not fit in 8 bits")),
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{unescape, Token};
+    use crate::dialect::Feature;
+    use crate::lexer::tokenize;
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(Ok(b"Hello, world".to_vec()), unescape("Hello, world"));
+        assert_eq!(
+            Ok(b"a\n\r\t\0\\\"'A".to_vec()),
+            unescape(r#"a\n\r\t\0\\\"\'\x41"#)
+        );
+        assert_eq!(Ok(vec![0xE9]), unescape("\u{E9}"));
+        assert!(unescape(r"\q").is_err());
+        assert!(unescape(r"\x4").is_err());
+        assert!(unescape(r"\xZZ").is_err());
+        assert!(unescape("合").is_err());
+    }
+
+    #[test]
+    fn test_feature() {
+        let source = "LOOP L_1 10 1_0 X\"1F 0x1F $1F 1FH 1FF 'AB 'A' \"A\"";
+        let features: Vec<Option<Feature>> = tokenize(source)
+            .iter()
+            .map(|(token, span)| token.feature(&source[span.clone()]))
+            .collect();
+        assert_eq!(
+            vec![
+                None,
+                Some(Feature::UnderscoreSymbol),
+                None,
+                Some(Feature::DigitSeparator),
+                None,
+                Some(Feature::PrefixedNumber),
+                Some(Feature::PrefixedNumber),
+                Some(Feature::PrefixedNumber),
+                Some(Feature::BareHexadecimal),
+                Some(Feature::LegacyCharacters),
+                Some(Feature::CharacterLiteral),
+                Some(Feature::StringLiteral),
+            ],
+            features
+        );
+        assert_eq!(None, Token::Comma.feature(","));
+    }
+}
@@ -0,0 +1,141 @@
+use crate::assembler::Program;
+use crate::diagnostic::line_column;
+use crate::instruction::MachineAddress;
+use crate::parser::ProgramLine;
+use crate::symbol;
+
+// A line which defines or uses a symbol
+#[derive(Debug, Eq, PartialEq)]
+pub struct Site<'a> {
+    pub line: usize,
+    pub column: usize,
+    // Source text of the instruction, without the label
+    pub instruction: &'a str,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct CrossReference<'a> {
+    pub name: &'a str,
+    pub address: MachineAddress,
+    pub definition: Site<'a>,
+    pub uses: Vec<Site<'a>>,
+}
+
+fn site<'a>(source: &'a str, line: &ProgramLine, offset: usize) -> Site<'a> {
+    let instruction = &source[line.instruction_span(source)];
+    let (line, column) = line_column(source, offset);
+    Site {
+        line,
+        column,
+        instruction,
+    }
+}
+
+// Finds where every symbol is defined and used, sorted by name
+pub fn cross_references<'a>(program: &'a Program, source: &'a str) -> Vec<CrossReference<'a>> {
+    let lines = &program.ast.lines;
+    let definitions = symbol::definitions(lines);
+    let mut xrefs: Vec<CrossReference> = symbol::create_symbol_table(lines)
+        .into_iter()
+        .map(|(name, address)| {
+            let definition = &lines[definitions[name]];
+            CrossReference {
+                name,
+                address,
+                definition: site(source, definition, definition.span.start),
+                uses: program
+                    .references
+                    .iter()
+                    .filter(|reference| reference.symbol == name)
+                    .map(|reference| site(source, &lines[reference.line], reference.span.start))
+                    .collect(),
+            }
+        })
+        .collect();
+    xrefs.sort_by_key(|xref| xref.name);
+    xrefs
+}
+
+// Formats a cross-reference listing
+pub fn format(xrefs: &[CrossReference]) -> String {
+    let mut listing = String::new();
+    for xref in xrefs {
+        listing += &format!("{}  {:04X}\n", xref.name, xref.address);
+        let sites = std::iter::once(("defined", &xref.definition))
+            .chain(xref.uses.iter().map(|site| ("used", site)));
+        for (kind, site) in sites {
+            let position = format!("{}:{}", site.line, site.column);
+            listing += &format!("    {kind:<8}{position:<10}{}\n", site.instruction);
+        }
+    }
+    listing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cross_references, format, Site};
+    use crate::assembler::analyze;
+    use crate::parser::Syntax;
+
+    const SOURCE: &str = "TITLE T
+LOOP:   L     1,X
+        BNZ   LOOP
+X:      DC    0
+PTR:    DC    X
+        END
+";
+
+    #[test]
+    fn test_cross_references() {
+        let program = analyze(SOURCE, Syntax::default()).unwrap();
+        let xrefs = cross_references(&program, SOURCE);
+        let names: Vec<&str> = xrefs.iter().map(|xref| xref.name).collect();
+        assert_eq!(vec!["LOOP", "PTR", "X"], names);
+        assert_eq!(
+            Site {
+                line: 2,
+                column: 1,
+                instruction: "L     1,X"
+            },
+            xrefs[0].definition
+        );
+        assert_eq!(
+            vec![Site {
+                line: 3,
+                column: 15,
+                instruction: "BNZ   LOOP"
+            }],
+            xrefs[0].uses
+        );
+        assert!(xrefs[1].uses.is_empty());
+        assert_eq!(2, xrefs[2].uses.len());
+    }
+
+    #[test]
+    fn test_format() {
+        let program = analyze(SOURCE, Syntax::default()).unwrap();
+        let expected = "LOOP  0000
+    defined 2:1       L     1,X
+    used    3:15      BNZ   LOOP
+PTR  0003
+    defined 5:1       DC    X
+X  0002
+    defined 4:1       DC    0
+    used    2:17      L     1,X
+    used    5:15      DC    X
+";
+        assert_eq!(expected, format(&cross_references(&program, SOURCE)));
+    }
+
+    #[test]
+    fn test_uses_on_one_line() {
+        let source = "TITLE T
+X:      DC    X,X
+        END
+";
+        let program = analyze(source, Syntax::default()).unwrap();
+        let xrefs = cross_references(&program, source);
+        let columns: Vec<usize> = xrefs[0].uses.iter().map(|site| site.column).collect();
+        assert_eq!(vec![15, 17], columns);
+    }
+}
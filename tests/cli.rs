@@ -0,0 +1,266 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn rm1asm(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rm1asm"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn corpus() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/corpus")
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rm1asm-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_corpus() {
+    let output = rm1asm(&["test-corpus", corpus().to_str().unwrap()]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{stdout}");
+    assert!(stdout.ends_with(" passed, 0 failed\n"));
+}
+
+#[test]
+fn test_corpus_mismatch() {
+    let dir = temp_dir("mismatch");
+    fs::copy(corpus().join("sum.asm"), dir.join("sum.asm")).unwrap();
+    let expected = fs::read_to_string(corpus().join("sum.b")).unwrap();
+    fs::write(
+        dir.join("sum.b"),
+        expected.replace("0007  000A", "0007  000B"),
+    )
+    .unwrap();
+
+    let output = rm1asm(&["test-corpus", dir.to_str().unwrap()]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!output.status.success());
+    assert!(stdout.contains("FAIL"));
+    assert!(stdout.contains("- 0007  000B"));
+    assert!(stdout.contains("+ 0007  000A"));
+    assert!(stdout.ends_with("0 passed, 1 failed\n"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_build_matches_corpus() {
+    for name in [
+        "sum", "echo", "data", "message", "numbers", "pseudo", "literal",
+    ] {
+        let source = corpus().join(format!("{name}.asm"));
+        let output = rm1asm(&["build", source.to_str().unwrap(), "-o", "-"]);
+        assert!(output.status.success());
+        let expected = fs::read_to_string(corpus().join(format!("{name}.b"))).unwrap();
+        assert_eq!(expected, String::from_utf8(output.stdout).unwrap());
+    }
+}
+
+#[test]
+fn test_listing() {
+    let dir = temp_dir("listing");
+    let source = corpus().join("pseudo.asm");
+    let image = dir.join("pseudo.b");
+    let output = rm1asm(&[
+        "build",
+        source.to_str().unwrap(),
+        "-o",
+        image.to_str().unwrap(),
+        "--listing",
+        "-",
+    ]);
+    assert!(output.status.success());
+    let listing = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        listing.contains("               12  DONE:   CLR   0\n000F  9C00                 LC 0,0\n")
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_listing_with_image_on_stdout() {
+    let source = corpus().join("pseudo.asm");
+    let output = rm1asm(&[
+        "build",
+        source.to_str().unwrap(),
+        "-o",
+        "-",
+        "--listing",
+        "-",
+    ]);
+    assert_eq!(Some(1), output.status.code());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("the memory image and --listing cannot share the standard output"));
+}
+
+#[test]
+fn test_org_regions_read_back() {
+    let dir = temp_dir("regions");
+    let source = dir.join("a.asm");
+    let image = dir.join("a.b");
+    fs::write(&source, "TITLE A\n  ORG 0x10\n  HLT\n  ORG 0\n  NOP\nEND\n").unwrap();
+    assert!(rm1asm(&["build", source.to_str().unwrap()])
+        .status
+        .success());
+    let output = rm1asm(&["disasm", image.to_str().unwrap()]);
+    assert!(output.status.success());
+
+    // Overlapping regions would write an address twice, which an image cannot be read with
+    fs::remove_file(&image).unwrap();
+    fs::write(
+        &source,
+        "TITLE A\n  ORG 0x10\n  HLT\n  ORG 0x10\n  NOP\nEND\n",
+    )
+    .unwrap();
+    let output = rm1asm(&["build", "--error-format", "short", source.to_str().unwrap()]);
+    assert_eq!(Some(1), output.status.code());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(":5:3: error: address 0010 is written twice"));
+    assert!(!image.exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_check_reports_errors() {
+    let dir = temp_dir("check");
+    let source = dir.join("error.asm");
+    fs::write(&source, "TITLE E\n  B NOWHERE\nEND\n").unwrap();
+
+    let output = rm1asm(&["check", "--error-format", "short", source.to_str().unwrap()]);
+    assert_eq!(Some(1), output.status.code());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(":2:5: error: unresolved symbol `NOWHERE`"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_out_dir_clash() {
+    let dir = temp_dir("clash");
+    for sub in ["a", "b"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+        fs::write(dir.join(sub).join("x.asm"), "TITLE X\n  HLT\nEND\n").unwrap();
+    }
+    let out_dir = dir.join("out");
+
+    let a = dir.join("a/x.asm");
+    let b = dir.join("b/x.asm");
+    let output = rm1asm(&[
+        "build",
+        a.to_str().unwrap(),
+        b.to_str().unwrap(),
+        "--out-dir",
+        out_dir.to_str().unwrap(),
+    ]);
+    assert_eq!(Some(1), output.status.code());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(&format!("{} and {}", a.display(), b.display())));
+    assert!(!out_dir.exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_global_options_before_subcommand() {
+    let dir = temp_dir("global");
+    let source = dir.join("a.asm");
+    fs::write(&source, "TITLE A\n  HLT\nEND\n").unwrap();
+
+    for flag in [&["--error-format", "short"][..], &["--decimal-org"]] {
+        let mut args = flag.to_vec();
+        args.extend(["check", source.to_str().unwrap()]);
+        let output = rm1asm(&args);
+        assert!(output.status.success());
+        assert!(!dir.join("a.b").exists());
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_shift_jis_source() {
+    let dir = temp_dir("sjis");
+    let source = dir.join("sjis.asm");
+    // "TITLE S ; 合計" in Shift_JIS
+    fs::write(&source, b"TITLE S ; \x8D\x87\x8C\x76\n  HLT\nEND\n").unwrap();
+    let source = source.to_str().unwrap();
+
+    let output = rm1asm(&["build", source, "-o", "-"]);
+    assert!(output.status.success());
+    assert_eq!(b"MM S\n0000  EF00".to_vec(), output.stdout);
+
+    let output = rm1asm(&["build", "--input-encoding", "utf-8", source, "-o", "-"]);
+    assert_eq!(Some(1), output.status.code());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.ends_with(": not a valid UTF-8 text\n"));
+
+    // Formatting keeps the encoding of the source
+    fs::write(
+        dir.join("sjis.asm"),
+        b"TITLE S ; \x8D\x87\x8C\x76\nHLT\nEND\n",
+    )
+    .unwrap();
+    assert!(rm1asm(&["fmt", source]).status.success());
+    let formatted = fs::read(source).unwrap();
+    assert!(formatted.ends_with(b"; \x8D\x87\x8C\x76\n        HLT\n        END\n"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_dialect() {
+    let dir = temp_dir("dialect");
+    let source = dir.join("a.asm");
+    fs::write(&source, "TITLE A\n  DC \"A\"\n  HLT\nEND\n").unwrap();
+
+    let output = rm1asm(&["check", "--dialect", "classic", source.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("string literals are not allowed in the classic dialect"));
+    let output = rm1asm(&["check", source.to_str().unwrap()]);
+    assert!(output.status.success());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_incbin() {
+    let dir = temp_dir("incbin");
+    let source = dir.join("a.asm");
+    fs::write(dir.join("table.bin"), [0x12, 0x34, 0x56]).unwrap();
+    fs::write(
+        &source,
+        "TITLE A\n  LA 0,DONE\n  INCBIN \"table.bin\"\nDONE: HLT\nEND\n",
+    )
+    .unwrap();
+
+    // The file is looked up next to the source program
+    let output = rm1asm(&["build", source.to_str().unwrap(), "-o", "-"]);
+    assert!(output.status.success());
+    assert_eq!(
+        "MM A\n0000  9803\n0001  1234\n0002  5600\n0003  EF00",
+        String::from_utf8(output.stdout).unwrap()
+    );
+    let output = rm1asm(&[
+        "build",
+        "--byte-order",
+        "little",
+        source.to_str().unwrap(),
+        "-o",
+        "-",
+    ]);
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("0001  3412\n0002  0056\n"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,17 @@
+; Places code and data with ORG
+        TITLE DATA
+        ORG   10
+START:  LA    1,TABLE
+        LEA   1,1(1)
+        BSR   SUB1
+        HLT
+SUB1:   SL    0,1
+        RET
+        ORG   20
+TABLE:  DC    X"1234
+        DC    O"17
+        DC    B"101
+        DC    'AB
+        DC    START
+        DS    2
+        END
@@ -0,0 +1,14 @@
+MM DATA
+0010  9910
+0011  A501
+0012  EA02
+0013  EF00
+0014  5001
+0015  EB00
+0020  1234
+0021  000F
+0022  0005
+0023  4142
+0024  0010
+0025  0000
+0026  0000
\ No newline at end of file
@@ -0,0 +1,9 @@
+; Copies characters from the card reader to the line printer until a period
+        TITLE ECHO
+LOOP:   RIO   CR
+        L     0,PERIOD
+        WIO   LPT
+        B     LOOP
+        HLT
+PERIOD: DC    X"2E
+        END
@@ -0,0 +1,7 @@
+MM ECHO
+0000  EC00
+0001  9004
+0002  ED01
+0003  E8FD
+0004  EF00
+0005  002E
\ No newline at end of file
@@ -0,0 +1,12 @@
+; Adds two 16-bit constants, which are loaded from literal pools
+        TITLE LITERAL
+        L     0,=X"1234"
+        B     NEXT
+        LTORG
+NEXT:   L     1,=X"0101"
+        ADD   0,1
+        ST    0,SUM
+        L     1,=X"1234"
+        HLT
+SUM:    DS    1
+        END
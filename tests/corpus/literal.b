@@ -0,0 +1,12 @@
+MM LITERAL
+0000  9002
+0001  E802
+0002  1234
+0003  9106
+0004  0001
+0005  9403
+0006  9104
+0007  EF00
+0008  0000
+0009  0101
+000A  1234
\ No newline at end of file
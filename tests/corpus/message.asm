@@ -0,0 +1,11 @@
+; Prints a zero-terminated message to the line printer
+        TITLE MESSAGE
+        LA    1,MSG
+LOOP:   LX    0,0(1)
+        BZ    DONE
+        WIO   LPT
+        LEA   1,1(1)
+        B     LOOP
+DONE:   HLT
+MSG:    DCZ   "Hello, world\n"
+        END
@@ -0,0 +1,22 @@
+MM MESSAGE
+0000  9907
+0001  B400
+0002  E104
+0003  ED01
+0004  A501
+0005  E8FC
+0006  EF00
+0007  0048
+0008  0065
+0009  006C
+000A  006C
+000B  006F
+000C  002C
+000D  0020
+000E  0077
+000F  006F
+0010  0072
+0011  006C
+0012  0064
+0013  000A
+0014  0000
\ No newline at end of file
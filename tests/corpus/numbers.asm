@@ -0,0 +1,11 @@
+; Writes the same numbers in every literal syntax
+        TITLE NUMBERS
+        ORG   $100
+DEC:    DC    4660,4_660
+HEX:    DC    X"1234,0x1234,0X12_34,$1234,1234H
+OCT:    DC    O"11064,0o11064
+BIN:    DC    B"1001000110100,0b1_0010_0011_0100
+        DS    0x2
+        LC    0,0x7F
+        HLT
+        END
@@ -0,0 +1,16 @@
+MM NUMBERS
+0100  1234
+0101  1234
+0102  1234
+0103  1234
+0104  1234
+0105  1234
+0106  1234
+0107  1234
+0108  1234
+0109  1234
+010A  1234
+010B  0000
+010C  0000
+010D  9C7F
+010E  EF00
\ No newline at end of file
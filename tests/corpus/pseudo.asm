@@ -0,0 +1,14 @@
+; Fills a table with a 16-bit pattern, using pseudo-instructions
+        TITLE PSEUDO
+        LI    0,0xA55A
+        LA    1,TABLE
+        LC    2,4
+LOOP:   STX   0,0(1)
+        INC   1
+        DEC   2
+        BNZ   LOOP
+        JMP   DONE
+TABLE:  DS    4
+DONE:   CLR   0
+        HLT
+        END
@@ -0,0 +1,18 @@
+MM PSEUDO
+0000  9CA5
+0001  5008
+0002  305A
+0003  9908
+0004  9E04
+0005  C400
+0006  0101
+0007  1201
+0008  E5FD
+0009  E901
+000A  000F
+000B  0000
+000C  0000
+000D  0000
+000E  0000
+000F  9C00
+0010  EF00
\ No newline at end of file
@@ -0,0 +1,15 @@
+; Sums a table of squares with an unrolled loop, using repeat blocks
+        TITLE REPEAT
+        LC    0,0
+        LA    1,TABLE
+        REPT  4,I
+        LX    2,I(1)
+        ADD   0,2
+        ENDR
+        ST    0,SUM
+        HLT
+TABLE:  IRP   V,<1,4,9,16>
+        DC    V
+        ENDR
+SUM:    DS    1
+        END
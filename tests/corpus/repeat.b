@@ -0,0 +1,18 @@
+MM REPEAT
+0000  9C00
+0001  990B
+0002  B600
+0003  0002
+0004  B601
+0005  0002
+0006  B602
+0007  0002
+0008  B603
+0009  0002
+000A  9406
+000B  EF00
+000C  0001
+000D  0004
+000E  0009
+000F  0010
+0010  0000
\ No newline at end of file
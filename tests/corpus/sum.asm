@@ -0,0 +1,12 @@
+; Sums up the integers from 1 to N
+        TITLE SUM
+        LC    0,0               ; sum
+        L     1,N               ; counter
+LOOP:   ADD   0,(1)
+        SUB   1,1
+        BNZ   LOOP
+        ST    0,RESULT
+        HLT
+N:      DC    10
+RESULT: DS    1
+        END
@@ -0,0 +1,10 @@
+MM SUM
+0000  9C00
+0001  9106
+0002  0400
+0003  1101
+0004  E5FE
+0005  9403
+0006  EF00
+0007  000A
+0008  0000
\ No newline at end of file